@@ -0,0 +1,38 @@
+//! Records build metadata for `please version --verbose`
+
+use std::{env, path::Path, process::Command, time::SystemTime};
+
+fn main() {
+    let commit = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+
+    // SOURCE_DATE_EPOCH keeps reproducible builds reproducible
+    let date = env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.parse().ok())
+        .map(|secs| SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(secs))
+        .unwrap_or_else(SystemTime::now);
+
+    println!("cargo:rustc-env=PLEASE_GIT_COMMIT={}", commit);
+    println!(
+        "cargo:rustc-env=PLEASE_BUILD_DATE={}",
+        humantime::format_rfc3339_seconds(date)
+    );
+    println!(
+        "cargo:rustc-env=PLEASE_TARGET={}",
+        env::var("TARGET").expect("cargo sets TARGET")
+    );
+
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    // Missing paths would make cargo rerun this on every build
+    for path in [".git/HEAD", ".git/refs"] {
+        if Path::new(path).exists() {
+            println!("cargo:rerun-if-changed={}", path);
+        }
+    }
+}
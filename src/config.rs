@@ -1,45 +1,345 @@
-use dirs::state_dir;
-use std::path::PathBuf;
+use crate::history_parser::{DEFAULT_SET_FLAGS, DEFAULT_SHEBANG};
+use anyhow::{bail, Context};
+use dirs::home_dir;
+use serde::Deserialize;
+use std::{
+    env,
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+};
 
 #[derive(Clone)]
 pub struct Config {
     pub state_dir: PathBuf,
     pub scripts_dir: PathBuf,
-    pub build_file_path: PathBuf,
+    /// Optional user settings, see `ConfigFile`
+    pub config_file_path: PathBuf,
+    /// First line of built scripts
+    pub shebang: String,
+    /// Flags for the `set` line of built scripts, defaults to `-e`.
+    /// Note that `-o pipefail` only works in bash (and zsh), not in plain sh.
+    pub set_flags: String,
+    /// Leave `cd` commands out of built scripts
+    pub drop_cd: bool,
+    /// Collapse immediately repeated commands into one
+    pub dedupe: bool,
+    /// Leave out commands that failed, only works with history that records exit codes
+    pub skip_failed: bool,
+    /// Put a comment saying when and from which history please built the script after the shebang
+    pub header: bool,
+    /// Write when each script ran and how it exited to `run.log` in the state dir
+    pub run_log: bool,
+    /// Commands that are never captured, in addition to the built-in ones
+    pub ignored_commands: Vec<String>,
+    /// History files read instead of the shell's own, the file names may contain `*`.
+    /// Used for history split across several files.
+    pub histfiles: Vec<PathBuf>,
+    /// Built scripts containing these need `--force`
+    pub dangerous_patterns: Vec<String>,
+    /// Lines containing these get a warning that they may have a secret in them
+    pub secret_patterns: Vec<String>,
+    /// Editor used when neither `VISUAL` nor `EDITOR` is set
+    pub default_editor: Option<String>,
 }
 
-impl Default for Config {
-    fn default() -> Self {
-        let state_dir = state_dir().expect("get state dir").join("please");
+const DEFAULT_DANGEROUS_PATTERNS: &[&str] = &["rm -rf", "dd", "mkfs", ":(){ :|:& };:"];
 
-        let config = Config {
-            scripts_dir: state_dir.join("scripts"),
-            build_file_path: state_dir.join("build.json"),
-            state_dir,
-        };
+const DEFAULT_SECRET_PATTERNS: &[&str] = &[
+    "AWS_SECRET",
+    "--password",
+    "TOKEN=",
+    "SECRET=",
+    "PASSWORD=",
+    "API_KEY=",
+];
 
-        config.ensure_state();
+enum SettingKind {
+    Text,
+    Bool,
+    List,
+}
+
+/// Keys of `config.toml` and the kind of value each takes, same as `ConfigFile`
+const SETTINGS: &[(&str, SettingKind)] = &[
+    ("shebang", SettingKind::Text),
+    ("scripts_dir", SettingKind::Text),
+    ("set_flags", SettingKind::Text),
+    ("drop_cd", SettingKind::Bool),
+    ("dedupe", SettingKind::Bool),
+    ("skip_failed", SettingKind::Bool),
+    ("header", SettingKind::Bool),
+    ("run_log", SettingKind::Bool),
+    ("ignored_commands", SettingKind::List),
+    ("histfiles", SettingKind::List),
+    ("dangerous_patterns", SettingKind::List),
+    ("secret_patterns", SettingKind::List),
+    ("default_editor", SettingKind::Text),
+];
 
-        config
+fn single_value<'a>(key: &str, values: &'a [String]) -> anyhow::Result<&'a str> {
+    match values {
+        [value] => Ok(value),
+        _ => bail!("`{}` takes a single value", key),
     }
 }
 
+fn unknown_setting(key: &str) -> String {
+    let keys = SETTINGS.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+    format!("Unknown setting `{}`, try one of: {}", key, keys.join(", "))
+}
+
+/// Settings that can be changed in `config.toml` in the state dir
+#[derive(Debug, Default, Deserialize)]
+struct ConfigFile {
+    shebang: Option<String>,
+    scripts_dir: Option<String>,
+    set_flags: Option<String>,
+    drop_cd: Option<bool>,
+    dedupe: Option<bool>,
+    skip_failed: Option<bool>,
+    header: Option<bool>,
+    run_log: Option<bool>,
+    ignored_commands: Option<Vec<String>>,
+    histfiles: Option<Vec<String>>,
+    dangerous_patterns: Option<Vec<String>>,
+    secret_patterns: Option<Vec<String>>,
+    default_editor: Option<String>,
+}
+
 impl Config {
-    #[allow(unused)]
-    pub fn from_base_dir(dir: impl Into<PathBuf>) -> Self {
+    /// Loads the user's config. Inside a project, see [`find_project_dir`], the project's
+    /// dir keeps the scripts and builds and its `config.toml` overrides the user's settings.
+    pub fn load() -> anyhow::Result<Self> {
+        let state_dir = find_state_dir(env::var_os("XDG_STATE_HOME"), home_dir())?;
+        let project_dir = env::current_dir()
+            .ok()
+            .and_then(|cwd| find_project_dir(&cwd, home_dir()));
+
+        Self::load_from(state_dir, project_dir)
+    }
+
+    /// Only the state dir in use is created, the user's one is just read inside a project
+    fn load_from(state_dir: PathBuf, project_dir: Option<PathBuf>) -> anyhow::Result<Self> {
+        let mut config = Self::read(state_dir)?;
+        if let Some(project_dir) = project_dir {
+            config.use_state_dir(project_dir)?;
+        }
+        config.ensure_state();
+
+        Ok(config)
+    }
+
+    /// Loads the config from the user's state dir, see [`find_state_dir`]
+    pub fn load_global() -> anyhow::Result<Self> {
+        let state_dir = find_state_dir(env::var_os("XDG_STATE_HOME"), home_dir())?;
+        Self::new(state_dir)
+    }
+
+    /// Config with its state in `dir/please`.
+    /// Tests use this to keep out of the real state dir.
+    pub fn from_base_dir(dir: impl Into<PathBuf>) -> anyhow::Result<Self> {
         let state_dir: PathBuf = dir.into();
 
-        let state_dir = state_dir.join("please");
+        Self::new(state_dir.join("please"))
+    }
+
+    fn new(state_dir: PathBuf) -> anyhow::Result<Self> {
+        let config = Self::read(state_dir)?;
+        config.ensure_state();
+
+        Ok(config)
+    }
 
-        let config = Config {
+    /// Config from `state_dir` without creating anything
+    fn read(state_dir: PathBuf) -> anyhow::Result<Self> {
+        let mut config = Config {
             scripts_dir: state_dir.join("scripts"),
-            build_file_path: state_dir.join("build.json"),
-            state_dir,
+            config_file_path: state_dir.join("config.toml"),
+            shebang: DEFAULT_SHEBANG.to_string(),
+            set_flags: DEFAULT_SET_FLAGS.to_string(),
+            drop_cd: false,
+            dedupe: false,
+            skip_failed: false,
+            header: false,
+            run_log: false,
+            ignored_commands: Vec::new(),
+            histfiles: Vec::new(),
+            dangerous_patterns: DEFAULT_DANGEROUS_PATTERNS
+                .iter()
+                .map(|pattern| pattern.to_string())
+                .collect(),
+            secret_patterns: DEFAULT_SECRET_PATTERNS
+                .iter()
+                .map(|pattern| pattern.to_string())
+                .collect(),
+            default_editor: None,
+            state_dir: state_dir.clone(),
         };
+        config.use_state_dir(state_dir)?;
 
-        config.ensure_state();
+        Ok(config)
+    }
+
+    /// Keeps the scripts and builds in `state_dir`, with the settings of its
+    /// `config.toml` on top of the current ones
+    fn use_state_dir(&mut self, state_dir: PathBuf) -> anyhow::Result<()> {
+        self.scripts_dir = state_dir.join("scripts");
+        self.config_file_path = state_dir.join("config.toml");
+        self.state_dir = state_dir;
+
+        self.load_config_file()?;
+        // The env var overrides both the default and the config file
+        if let Some(dir) = env::var("PLEASE_SCRIPTS_DIR")
+            .ok()
+            .filter(|dir| !dir.is_empty())
+        {
+            self.scripts_dir = expand_path(&dir);
+        }
+
+        Ok(())
+    }
+
+    /// Every build in progress has its own `build-<name>.json` in the state dir
+    pub fn build_file_path(&self, name: &str) -> PathBuf {
+        self.state_dir.join(format!("build-{name}.json"))
+    }
+
+    /// Where runs are logged when `run_log` is on
+    pub fn run_log_path(&self) -> PathBuf {
+        self.state_dir.join("run.log")
+    }
+
+    /// Returns the names of the builds in progress in alphabetical order
+    pub fn active_builds(&self) -> Vec<String> {
+        let Ok(entries) = std::fs::read_dir(&self.state_dir) else {
+            return Vec::new();
+        };
+        let mut names = entries
+            .filter_map(Result::ok)
+            .filter_map(|entry| {
+                let file_name = entry.file_name();
+                let name = file_name.to_str()?.strip_prefix("build-")?;
+                Some(name.strip_suffix(".json")?.to_string())
+            })
+            .collect::<Vec<_>>();
+        names.sort();
 
-        config
+        names
+    }
+
+    /// Returns the value of a setting in use, lists have one value per line
+    pub fn get(&self, key: &str) -> anyhow::Result<String> {
+        let value = match key {
+            "shebang" => self.shebang.clone(),
+            "scripts_dir" => self.scripts_dir.display().to_string(),
+            "set_flags" => self.set_flags.clone(),
+            "drop_cd" => self.drop_cd.to_string(),
+            "dedupe" => self.dedupe.to_string(),
+            "skip_failed" => self.skip_failed.to_string(),
+            "header" => self.header.to_string(),
+            "run_log" => self.run_log.to_string(),
+            "ignored_commands" => self.ignored_commands.join("\n"),
+            "histfiles" => self
+                .histfiles
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect::<Vec<_>>()
+                .join("\n"),
+            "dangerous_patterns" => self.dangerous_patterns.join("\n"),
+            "secret_patterns" => self.secret_patterns.join("\n"),
+            "default_editor" => self.default_editor.clone().unwrap_or_default(),
+            _ => bail!(unknown_setting(key)),
+        };
+
+        Ok(value)
+    }
+
+    /// Sets `key` in `config.toml`, creating the file if needed.
+    /// Lists take all of `values`, other settings exactly one.
+    /// Note that comments in the file are not kept.
+    pub fn set(&self, key: &str, values: &[String]) -> anyhow::Result<()> {
+        let kind = SETTINGS
+            .iter()
+            .find(|(name, _)| *name == key)
+            .map(|(_, kind)| kind)
+            .with_context(|| unknown_setting(key))?;
+        let value = match kind {
+            SettingKind::List => toml::Value::from(values.to_vec()),
+            SettingKind::Text => toml::Value::from(single_value(key, values)?),
+            SettingKind::Bool => {
+                let value = single_value(key, values)?;
+                let value = value
+                    .parse::<bool>()
+                    .ok()
+                    .with_context(|| format!("`{}` must be true or false, not `{}`", key, value))?;
+                toml::Value::Boolean(value)
+            }
+        };
+
+        let path = &self.config_file_path;
+        let mut file = match fs::read_to_string(path) {
+            Ok(content) => toml::from_str::<toml::Table>(&content)
+                .with_context(|| format!("parse {}", path.display()))?,
+            Err(_) => toml::Table::new(),
+        };
+        file.insert(key.to_string(), value);
+        let content = toml::to_string(&file).context("serialize config")?;
+
+        fs::write(path, content).with_context(|| format!("write {}", path.display()))
+    }
+
+    fn load_config_file(&mut self) -> anyhow::Result<()> {
+        if !self.config_file_path.exists() {
+            return Ok(());
+        }
+
+        let path = self.config_file_path.display();
+        let content = std::fs::read_to_string(&self.config_file_path)
+            .with_context(|| format!("read {path}"))?;
+        let file: ConfigFile = toml::from_str(&content).with_context(|| format!("parse {path}"))?;
+
+        if let Some(shebang) = file.shebang {
+            self.shebang = shebang;
+        }
+        if let Some(dir) = file.scripts_dir {
+            self.scripts_dir = expand_path(&dir);
+        }
+        if let Some(set_flags) = file.set_flags {
+            self.set_flags = set_flags;
+        }
+        if let Some(drop_cd) = file.drop_cd {
+            self.drop_cd = drop_cd;
+        }
+        if let Some(dedupe) = file.dedupe {
+            self.dedupe = dedupe;
+        }
+        if let Some(skip_failed) = file.skip_failed {
+            self.skip_failed = skip_failed;
+        }
+        if let Some(header) = file.header {
+            self.header = header;
+        }
+        if let Some(run_log) = file.run_log {
+            self.run_log = run_log;
+        }
+        if let Some(commands) = file.ignored_commands {
+            self.ignored_commands = commands;
+        }
+        if let Some(histfiles) = file.histfiles {
+            self.histfiles = histfiles.iter().map(|path| expand_path(path)).collect();
+        }
+        if let Some(patterns) = file.dangerous_patterns {
+            self.dangerous_patterns = patterns;
+        }
+        if let Some(patterns) = file.secret_patterns {
+            self.secret_patterns = patterns;
+        }
+        if let Some(editor) = file.default_editor {
+            self.default_editor = Some(editor);
+        }
+
+        Ok(())
     }
     fn ensure_state(&self) {
         if !self.state_dir.exists() {
@@ -64,6 +364,86 @@ impl Config {
     }
 }
 
+/// Walks up from `cwd` for a `.please` dir to use as the state dir of a project.
+/// `~/.please` is skipped since it is the user's own state dir.
+fn find_project_dir(cwd: &Path, home: Option<PathBuf>) -> Option<PathBuf> {
+    let user_dir = home.map(|home| home.join(".please"));
+    cwd.ancestors()
+        .map(|dir| dir.join(".please"))
+        .find(|dir| dir.is_dir() && Some(dir) != user_dir.as_ref())
+}
+
+/// Picks the state dir from the first of these that resolves:
+/// `$XDG_STATE_HOME/please`, `~/.local/state/please` if `~/.local/state` exists, `~/.please`
+fn find_state_dir(
+    xdg_state_home: Option<OsString>,
+    home: Option<PathBuf>,
+) -> anyhow::Result<PathBuf> {
+    // Relative paths are invalid in XDG variables
+    if let Some(dir) = xdg_state_home
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+    {
+        return Ok(dir.join("please"));
+    }
+
+    let home = home.context("Couldn't find your home directory, set XDG_STATE_HOME")?;
+    let local_state = home.join(".local").join("state");
+    if local_state.is_dir() {
+        return Ok(local_state.join("please"));
+    }
+
+    Ok(home.join(".please"))
+}
+
+/// Expands a leading `~` to the home directory and `$VAR` or `${VAR}`
+/// to the value of the environment variable
+fn expand_path(path: &str) -> PathBuf {
+    expand_with(path, home_dir(), |var| env::var(var).ok())
+}
+
+/// Unset variables are left as they are
+fn expand_with(
+    path: &str,
+    home: Option<PathBuf>,
+    lookup: impl Fn(&str) -> Option<String>,
+) -> PathBuf {
+    let mut res = String::new();
+    let mut rest = path;
+    if let Some(home) = home {
+        if rest == "~" || rest.starts_with("~/") {
+            res.push_str(&home.to_string_lossy());
+            rest = &rest[1..];
+        }
+    }
+
+    while let Some(start) = rest.find('$') {
+        res.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let (name, len) = match after.strip_prefix('{') {
+            Some(braced) => match braced.find('}') {
+                Some(end) => (&braced[..end], end + 2),
+                None => ("", 0),
+            },
+            None => {
+                let end = after
+                    .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                    .unwrap_or(after.len());
+                (&after[..end], end)
+            }
+        };
+
+        match lookup(name).filter(|_| !name.is_empty()) {
+            Some(value) => res.push_str(&value),
+            None => res.push_str(&rest[start..start + 1 + len]),
+        }
+        rest = &after[len..];
+    }
+    res.push_str(rest);
+
+    PathBuf::from(res)
+}
+
 #[cfg(test)]
 mod should {
     use super::*;
@@ -72,7 +452,7 @@ mod should {
     #[test]
     fn create_config() {
         fs::create_dir("/tmp/config").unwrap();
-        let config = Config::from_base_dir("/tmp/config");
+        let config = Config::from_base_dir("/tmp/config").unwrap();
 
         assert!(config.scripts_dir.exists());
         assert!(config.state_dir.exists());
@@ -84,4 +464,203 @@ mod should {
 
         fs::remove_dir_all("/tmp/config").unwrap();
     }
+
+    #[test]
+    fn load_config_file() {
+        fs::create_dir_all("/tmp/config2/please").unwrap();
+        fs::write(
+            "/tmp/config2/please/config.toml",
+            "shebang = \"#!/bin/bash\"\nignored_commands = [\"pls list\"]\n",
+        )
+        .unwrap();
+        let config = Config::from_base_dir("/tmp/config2").unwrap();
+
+        assert_eq!(config.shebang, "#!/bin/bash");
+        assert_eq!(config.set_flags, DEFAULT_SET_FLAGS);
+        assert_eq!(config.ignored_commands, vec!["pls list"]);
+
+        fs::remove_dir_all("/tmp/config2").unwrap();
+    }
+
+    #[test]
+    fn fail_on_malformed_config_file() {
+        fs::create_dir_all("/tmp/config7/please").unwrap();
+        fs::write(
+            "/tmp/config7/please/config.toml",
+            "shebang = \"#!/bin/bash\n",
+        )
+        .unwrap();
+        assert!(Config::from_base_dir("/tmp/config7").is_err());
+
+        fs::write("/tmp/config7/please/config.toml", "dedupe = \"yes\"\n").unwrap();
+        assert!(Config::from_base_dir("/tmp/config7").is_err());
+
+        fs::remove_dir_all("/tmp/config7").unwrap();
+    }
+
+    #[test]
+    fn get_and_set_settings() {
+        let config = Config::from_base_dir("/tmp/config5").unwrap();
+        assert_eq!(config.get("shebang").unwrap(), DEFAULT_SHEBANG);
+
+        config.set("shebang", &["#!/bin/bash".to_string()]).unwrap();
+        config.set("dedupe", &["true".to_string()]).unwrap();
+        let commands = ["pls".to_string(), "htop".to_string()];
+        config.set("ignored_commands", &commands).unwrap();
+
+        let config = Config::from_base_dir("/tmp/config5").unwrap();
+        assert_eq!(config.get("shebang").unwrap(), "#!/bin/bash");
+        assert_eq!(config.get("dedupe").unwrap(), "true");
+        assert_eq!(config.get("ignored_commands").unwrap(), "pls\nhtop");
+
+        assert_eq!(config.get("default_editor").unwrap(), "");
+        config.set("default_editor", &["vim".to_string()]).unwrap();
+        let config = Config::from_base_dir("/tmp/config5").unwrap();
+        assert_eq!(config.get("default_editor").unwrap(), "vim");
+        assert_eq!(config.default_editor.as_deref(), Some("vim"));
+
+        assert!(config.get("nope").is_err());
+        assert!(config.set("nope", &["x".to_string()]).is_err());
+        assert!(config.set("dedupe", &["maybe".to_string()]).is_err());
+        assert!(config.set("shebang", &commands).is_err());
+
+        fs::remove_dir_all("/tmp/config5").unwrap();
+    }
+
+    #[test]
+    fn list_active_builds() {
+        let config = Config::from_base_dir("/tmp/config3").unwrap();
+        assert!(config.active_builds().is_empty());
+
+        fs::write(config.build_file_path("foo"), "{}").unwrap();
+        fs::write(config.build_file_path("bar"), "{}").unwrap();
+        fs::write(config.state_dir.join("config.toml"), "").unwrap();
+
+        assert_eq!(config.active_builds(), vec!["bar", "foo"]);
+
+        fs::remove_dir_all("/tmp/config3").unwrap();
+    }
+
+    #[test]
+    fn expand_paths() {
+        let home = Some(PathBuf::from("/home/me"));
+        let lookup = |var: &str| (var == "HOME").then(|| "/home/me".to_string());
+
+        assert_eq!(
+            expand_with("~/foo", home.clone(), lookup),
+            PathBuf::from("/home/me/foo")
+        );
+        assert_eq!(
+            expand_with("$HOME/foo", home.clone(), lookup),
+            PathBuf::from("/home/me/foo")
+        );
+        assert_eq!(
+            expand_with("${HOME}/foo", home.clone(), lookup),
+            PathBuf::from("/home/me/foo")
+        );
+        assert_eq!(
+            expand_with("/a/~/b", home.clone(), lookup),
+            PathBuf::from("/a/~/b")
+        );
+        assert_eq!(
+            expand_with("$NOPE/foo", home.clone(), lookup),
+            PathBuf::from("$NOPE/foo")
+        );
+        assert_eq!(expand_with("${HOME", home, lookup), PathBuf::from("${HOME"));
+    }
+
+    #[test]
+    fn find_state_dir_in_order() {
+        let home = PathBuf::from("/tmp/config4");
+        fs::create_dir_all(&home).unwrap();
+
+        let xdg = Some(OsString::from("/tmp/config4/xdg"));
+        assert_eq!(
+            find_state_dir(xdg, Some(home.clone())).unwrap(),
+            home.join("xdg/please")
+        );
+        let relative = Some(OsString::from("relative/xdg"));
+        assert_eq!(
+            find_state_dir(relative, Some(home.clone())).unwrap(),
+            home.join(".please")
+        );
+        assert_eq!(
+            find_state_dir(Some(OsString::new()), Some(home.clone())).unwrap(),
+            home.join(".please")
+        );
+
+        fs::create_dir_all(home.join(".local/state")).unwrap();
+        assert_eq!(
+            find_state_dir(None, Some(home.clone())).unwrap(),
+            home.join(".local/state/please")
+        );
+        assert!(find_state_dir(None, None).is_err());
+
+        fs::remove_dir_all(&home).unwrap()
+    }
+
+    #[test]
+    fn project_overrides_user_settings() {
+        fs::create_dir_all("/tmp/config8/please").unwrap();
+        fs::write(
+            "/tmp/config8/please/config.toml",
+            "shebang = \"#!/bin/bash\"\nscripts_dir = \"/tmp/config8/scripts\"\nignored_commands = [\"htop\"]\n",
+        )
+        .unwrap();
+        let project = PathBuf::from("/tmp/config8/project/.please");
+        fs::create_dir_all(&project).unwrap();
+        fs::write(project.join("config.toml"), "dedupe = true\n").unwrap();
+
+        let mut config = Config::from_base_dir("/tmp/config8").unwrap();
+        config.use_state_dir(project.clone()).unwrap();
+        assert_eq!(config.state_dir, project);
+        assert_eq!(config.scripts_dir, project.join("scripts"));
+        assert_eq!(config.shebang, "#!/bin/bash");
+        assert_eq!(config.ignored_commands, ["htop"]);
+        assert!(config.dedupe);
+
+        fs::remove_dir_all("/tmp/config8").unwrap()
+    }
+
+    #[test]
+    fn project_leaves_user_dirs_alone() {
+        fs::remove_dir_all("/tmp/config9").unwrap_or_default();
+        let project = PathBuf::from("/tmp/config9/project/.please");
+        fs::create_dir_all(&project).unwrap();
+
+        let config =
+            Config::load_from(PathBuf::from("/tmp/config9/please"), Some(project.clone())).unwrap();
+        assert!(!Path::new("/tmp/config9/please").exists());
+        assert!(project.join("scripts").is_dir());
+        assert_eq!(config.state_dir, project);
+
+        fs::remove_dir_all("/tmp/config9").unwrap()
+    }
+
+    #[test]
+    fn find_project_dir_upwards() {
+        let home = PathBuf::from("/tmp/config6");
+        let project = home.join("project");
+        fs::create_dir_all(project.join("src/deep")).unwrap();
+        fs::create_dir_all(home.join(".please")).unwrap();
+
+        assert_eq!(
+            find_project_dir(&project.join("src"), Some(home.clone())),
+            None
+        );
+        // Without a home the user's dir is just another project
+        assert_eq!(find_project_dir(&project, None), Some(home.join(".please")));
+
+        fs::create_dir_all(project.join(".please")).unwrap();
+        assert_eq!(
+            find_project_dir(&project.join("src/deep"), Some(home.clone())),
+            Some(project.join(".please"))
+        );
+        assert_eq!(
+            find_project_dir(&project, Some(home.clone())),
+            Some(project.join(".please"))
+        );
+
+        fs::remove_dir_all(&home).unwrap()
+    }
 }
@@ -0,0 +1,101 @@
+use owo_colors::OwoColorize;
+
+/// Words colored as keywords, the control flow of sh and the builtins please writes
+const KEYWORDS: &[&str] = &[
+    "if", "then", "else", "elif", "fi", "for", "while", "until", "do", "done", "case", "esac",
+    "in", "function", "return", "exit", "set", "export", "local", "read",
+];
+
+/// Colors sh syntax for the terminal: comments are dimmed, keywords magenta,
+/// strings green and variables cyan. This is a rough guess for reading scripts,
+/// not a full parser, e.g. strings spanning several lines are not recognized.
+pub fn highlight_sh(script: &str) -> String {
+    script
+        .split('\n')
+        .map(highlight_line)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn highlight_line(line: &str) -> String {
+    let mut res = String::new();
+    let mut word = String::new();
+    let mut chars = line.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let starts_word = word.is_empty();
+        match c {
+            '#' if starts_word => {
+                res.push_str(&(&line[i..]).dimmed().to_string());
+                return res;
+            }
+            '\'' | '"' => {
+                push_word(&mut res, &mut word);
+                let mut string = String::from(c);
+                let mut escaped = false;
+                for (_, next) in chars.by_ref() {
+                    string.push(next);
+                    if next == c && !(escaped && c == '"') {
+                        break;
+                    }
+                    escaped = next == '\\' && !escaped;
+                }
+                res.push_str(&string.green().to_string());
+            }
+            '$' => {
+                push_word(&mut res, &mut word);
+                let mut var = String::from(c);
+                while let Some((_, next)) = chars.next_if(|(_, next)| is_var_char(*next)) {
+                    var.push(next);
+                    if next == '}' {
+                        break;
+                    }
+                }
+                res.push_str(&var.cyan().to_string());
+            }
+            c if c.is_whitespace() || ";|&()".contains(c) => {
+                push_word(&mut res, &mut word);
+                res.push(c);
+            }
+            c => word.push(c),
+        }
+    }
+    push_word(&mut res, &mut word);
+
+    res
+}
+
+fn is_var_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "_{}?@#*!-".contains(c)
+}
+
+fn push_word(res: &mut String, word: &mut String) {
+    if KEYWORDS.contains(&word.as_str()) {
+        res.push_str(&word.magenta().to_string());
+    } else {
+        res.push_str(word);
+    }
+    word.clear();
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+
+    #[test]
+    fn highlight_sh_tokens() {
+        assert_eq!(highlight_sh("#!/bin/sh"), "#!/bin/sh".dimmed().to_string());
+        assert_eq!(highlight_sh("ls -la"), "ls -la");
+        assert_eq!(highlight_sh("set -e"), format!("{} -e", "set".magenta()));
+        assert_eq!(
+            highlight_sh("echo \"hi # there\" # note"),
+            format!("echo {} {}", "\"hi # there\"".green(), "# note".dimmed())
+        );
+        assert_eq!(
+            highlight_sh("cd ${DIR}/src; ls $1"),
+            format!("cd {}/src; ls {}", "${DIR}".cyan(), "$1".cyan())
+        );
+        // Words only containing a keyword are left alone
+        assert_eq!(highlight_sh("git done-list"), "git done-list");
+        assert_eq!(highlight_sh("a\nfi"), format!("a\n{}", "fi".magenta()));
+    }
+}
@@ -1,23 +1,318 @@
-use crate::script::Variable;
+use anyhow::bail;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::script::{VarKind, Variable};
 
 pub trait HistoryParser {
-    fn parse_history(
-        &self,
-        history: String,
-        variables: &Vec<Variable>,
-    ) -> anyhow::Result<Vec<String>>;
+    fn parse_history(&self, history: String, variables: &[Variable])
+        -> anyhow::Result<Vec<String>>;
+
+    /// Finds the most recent regular command of the build that is not excluded
+    /// and returns its index from the start of the build along with the command
+    fn last_command(&self, history: String) -> Option<(usize, String)>;
+
+    /// Returns the commands of the build from oldest to newest as they are in
+    /// history, before prompts and variables are turned into script lines
+    fn captured_history(&self, history: String) -> Vec<CapturedLine>;
+}
+
+/// A command in the part of history a build captures
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CapturedLine {
+    pub command: String,
+    /// False when the command is left out of the script, e.g. ignored or undone
+    pub kept: bool,
+}
+
+/// Shell specific parts of turning history into a script,
+/// the script parts default to sh syntax
+trait Shell {
+    /// Splits the history file into entries, by default one per line
+    fn entries(history: &str) -> Vec<String> {
+        history.lines().map(String::from).collect()
+    }
+    /// Tells apart lines with a command from metadata lines in the history file
+    fn is_command(_line: &str) -> bool {
+        true
+    }
+    /// Extracts the command from a line in the history file
+    fn command(line: &str) -> String;
+    /// Tells if the command on `line` exited with an error, used with
+    /// [`ParseOptions::skip_failed`]. None of the supported history
+    /// formats record exit codes so commands are never seen as failed.
+    fn failed(_line: &str) -> bool {
+        false
+    }
+    /// Line that makes the script stop on the first error,
+    /// `set_flags` are the flags given to `set` in sh like shells
+    fn preamble(set_flags: &str) -> String {
+        format!("set {}\n", set_flags)
+    }
+
+    /// Command that prompts the user for the value of `var`
+    fn prompt(prompt: &str, var: &str) -> String {
+        let mut escaped = String::new();
+        for c in prompt.chars() {
+            if matches!(c, '"' | '\\' | '$' | '`') {
+                escaped.push('\\');
+            }
+            escaped.push(c);
+        }
+        format!("read -p \"{} \" {}", escaped, var)
+    }
+    /// Command that exits the script when `var` is not a valid `kind`
+    fn validate(var: &str, kind: VarKind) -> Option<String> {
+        match kind {
+            VarKind::String => None,
+            VarKind::Int => Some(format!(
+                "case \"${{{var}#-}}\" in ''|*[!0-9]*) echo \"{var} must be a number\" >&2; exit 1 ;; esac"
+            )),
+            VarKind::Path => Some(format!(
+                "[ -e \"${var}\" ] || {{ echo \"${var} does not exist\" >&2; exit 1; }}"
+            )),
+        }
+    }
+
+    /// Command that sets `var` to `default` when it was left empty
+    fn default_value(var: &str, default: &str) -> String {
+        let default = default.replace('\'', "'\\''");
+        format!("{var}=${{{var}:-'{default}'}}")
+    }
+    /// Command that exports `var` with the value it had during the build
+    fn export(var: &str, value: &str) -> String {
+        let value = value.replace('\'', "'\\''");
+        format!("export {var}='{value}'")
+    }
 }
 
 struct Zsh;
 
+impl Shell for Zsh {
+    /// Zsh writes the newlines of multi-line commands as `\` at the end of the line
+    fn entries(history: &str) -> Vec<String> {
+        let mut entries = vec![];
+        let mut current: Option<String> = None;
+        for line in history.lines() {
+            let (text, continues) = match line.strip_suffix('\\') {
+                Some(text) => (text, true),
+                None => (line, false),
+            };
+            match &mut current {
+                Some(entry) => {
+                    entry.push('\n');
+                    entry.push_str(text);
+                }
+                None => current = Some(text.to_string()),
+            }
+            if !continues {
+                entries.extend(current.take());
+            }
+        }
+        entries.extend(current);
+
+        entries
+    }
+
+    fn command(line: &str) -> String {
+        let line = line.trim();
+        match zsh_metadata_len(line) {
+            Some(len) => line[len..].to_string(),
+            None => line.to_string(),
+        }
+    }
+}
+
+/// Bash history has one command per line, with `HISTTIMEFORMAT`
+/// each one comes after a `#<timestamp>` line
+struct Bash;
+
+impl Shell for Bash {
+    /// Keeps each `#<timestamp>` line in the same entry as the command after it
+    fn entries(history: &str) -> Vec<String> {
+        let mut entries = vec![];
+        let mut timestamp: Option<&str> = None;
+        for line in history.lines() {
+            if is_bash_timestamp(line) {
+                // A timestamp without a command is kept as an entry of its own
+                entries.extend(timestamp.replace(line).map(String::from));
+                continue;
+            }
+            match timestamp.take() {
+                Some(timestamp) => entries.push(format!("{timestamp}\n{line}")),
+                None => entries.push(line.to_string()),
+            }
+        }
+        entries.extend(timestamp.map(String::from));
+
+        entries
+    }
+
+    fn is_command(line: &str) -> bool {
+        !is_bash_timestamp(line)
+    }
+
+    fn command(line: &str) -> String {
+        let command = match line.split_once('\n') {
+            Some((timestamp, command)) if is_bash_timestamp(timestamp) => command,
+            _ => line,
+        };
+        command.trim().to_string()
+    }
+}
+
+fn is_bash_timestamp(line: &str) -> bool {
+    let timestamp = line.trim().strip_prefix('#');
+    timestamp.is_some_and(|ts| !ts.is_empty() && ts.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Fish history is YAML like, commands are on `- cmd: ` lines
+/// followed by `when:` and `paths:` lines
+struct Fish;
+
+impl Shell for Fish {
+    fn is_command(line: &str) -> bool {
+        line.starts_with("- cmd: ")
+    }
+
+    fn command(line: &str) -> String {
+        let cmd = line.trim_end().trim_start_matches("- cmd: ");
+        // Fish escapes backslashes and newlines
+        let mut res = String::new();
+        let mut chars = cmd.chars();
+        while let Some(c) = chars.next() {
+            match (c, chars.clone().next()) {
+                ('\\', Some('\\')) => {
+                    res.push('\\');
+                    chars.next();
+                }
+                ('\\', Some('n')) => {
+                    res.push('\n');
+                    chars.next();
+                }
+                _ => res.push(c),
+            }
+        }
+
+        res
+    }
+}
+
+/// Returns the length of the `: <timestamp>:<duration>;` prefix zsh writes
+/// with `EXTENDED_HISTORY`, or `None` for plain command lines
+fn zsh_metadata_len(line: &str) -> Option<usize> {
+    let rest = line.strip_prefix(": ")?;
+    let (timestamp, rest) = rest.split_once(':')?;
+    let (duration, _) = rest.split_once(';')?;
+    let is_number = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_digit());
+    if !is_number(timestamp) || !is_number(duration) {
+        return None;
+    }
+
+    // Only the first `;` separates the metadata from the command
+    Some(2 + timestamp.len() + 1 + duration.len() + 1)
+}
+
+/// PowerShell history (ConsoleHost_history.txt) has one plain command per line
+struct PowerShell;
+
+impl Shell for PowerShell {
+    fn command(line: &str) -> String {
+        line.trim().to_string()
+    }
+
+    fn preamble(_set_flags: &str) -> String {
+        "$ErrorActionPreference = \"Stop\"\n".to_string()
+    }
+
+    fn prompt(prompt: &str, var: &str) -> String {
+        let prompt = prompt
+            .replace('`', "``")
+            .replace('$', "`$")
+            .replace('"', "`\"");
+        format!("${} = Read-Host \"{}\"", var, prompt)
+    }
+
+    fn validate(var: &str, kind: VarKind) -> Option<String> {
+        match kind {
+            VarKind::String => None,
+            VarKind::Int => Some(format!(
+                "if (${var} -notmatch '^-?[0-9]+$') {{ throw \"{var} must be a number\" }}"
+            )),
+            VarKind::Path => Some(format!(
+                "if (-not (Test-Path ${var})) {{ throw \"${var} does not exist\" }}"
+            )),
+        }
+    }
+
+    fn default_value(var: &str, default: &str) -> String {
+        let default = default.replace('\'', "''");
+        format!("if (-not ${var}) {{ ${var} = '{default}' }}")
+    }
+
+    fn export(var: &str, value: &str) -> String {
+        let value = value.replace('\'', "''");
+        format!("$env:{var} = '{value}'")
+    }
+}
+
+/// Options that change what the generated script looks like
+#[derive(Debug, Clone)]
+pub struct ParseOptions {
+    pub shebang: String,
+    /// Flags for the `set` line after the shebang, e.g. `-euo pipefail`
+    pub set_flags: String,
+    /// Indexes of commands counted from the start of the build that are left out
+    pub excluded: Vec<usize>,
+    /// Leave out plain `cd` commands instead of collapsing them
+    pub drop_cd: bool,
+    /// Collapse immediately repeated commands into one, other duplicates are kept
+    pub dedupe: bool,
+    /// Leave out commands that failed, see `Shell::failed`
+    pub skip_failed: bool,
+    /// Commands that are ignored in addition to the built-in ones,
+    /// matched the same way as `IGNORED_COMMANDS`
+    pub ignored_commands: Vec<String>,
+    /// Name of the build, when set only its own `please build <name>`
+    /// counts as the start so other builds started later don't cut it short
+    pub build_name: Option<String>,
+    /// Take this many of the latest commands instead of the ones
+    /// after `please build <name>`, ignored commands and prompts are not counted
+    pub from_last: Option<usize>,
+    /// Format of the history, see [`get_parser`]
+    pub history_shell: Option<HistoryShell>,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            shebang: DEFAULT_SHEBANG.to_string(),
+            set_flags: DEFAULT_SET_FLAGS.to_string(),
+            excluded: Vec::new(),
+            drop_cd: false,
+            dedupe: false,
+            skip_failed: false,
+            ignored_commands: Vec::new(),
+            build_name: None,
+            from_last: None,
+            history_shell: None,
+        }
+    }
+}
+
 struct Parser<Shell> {
     shell: std::marker::PhantomData<Shell>,
+    options: ParseOptions,
 }
 
-const SHEBANG: &str = "#!/bin/sh\n";
+pub const DEFAULT_SHEBANG: &str = "#!/bin/sh";
+pub const DEFAULT_SET_FLAGS: &str = "-e";
+/// Number of lines `parse_history` puts before the captured commands (shebang and preamble)
+pub const PREAMBLE_LEN: usize = 2;
 const BUILD_CMD: &str = "please build";
 const IGNORED_COMMANDS: &[&str] = &[
     "please current",
+    "please status",
     "please list",
     "please build",
     "please build --help",
@@ -26,88 +321,456 @@ const IGNORED_COMMANDS: &[&str] = &[
     "cargo run -- current",
     "please ask --help",
     "please ask -h",
+    "please undo",
+    "please remember",
+    "please skip",
+    "please last",
+    "please history",
+    "please mark-start",
+    "please mark-end",
+    "please search",
+    "please show",
+    "please which",
+    "please doctor",
+    "please log",
+    "please stats",
+    "please version",
+    "please config",
+    "please clean",
 ];
 
-impl HistoryParser for Parser<Zsh> {
+/// Commands marking the exact region of history to capture
+const MARK_START_CMD: &str = "please mark-start";
+const MARK_END_CMD: &str = "please mark-end";
+
+/// Trailing comment that leaves a single command out of the build
+/// ls -la # please:skip -> not captured
+const SKIP_MARKER: &str = "# please:skip";
+
+impl<S: Shell> HistoryParser for Parser<S> {
     fn parse_history(
         &self,
         history: String,
-        variables: &Vec<Variable>,
+        variables: &[Variable],
     ) -> anyhow::Result<Vec<String>> {
         let mut res = vec![];
-        let mut var_iter = variables.iter().rev();
-        for line in history
-            .lines()
-            .rev()
-            .map(|line| line.trim().split(";").skip(1).collect::<String>())
-            .take_while(|line| !is_start_of_build(line))
-        {
+        // Remembered variables have no `please ask` line in history
+        let asked = variables
+            .iter()
+            .filter(|var| var.remembered.is_none())
+            .collect::<Vec<_>>();
+        // Variables asked for again are only read the first time
+        let mut var_iter = asked
+            .iter()
+            .enumerate()
+            .map(|(i, var)| (*var, asked[..i].iter().any(|prev| prev.value == var.value)))
+            .rev();
+        let captured = captured_commands::<S>(&history, &self.options);
+        // Prompts with no recorded variable, numbered from the oldest one
+        let mut unnamed = captured
+            .iter()
+            .filter(|(index, cmd)| {
+                !self.options.excluded.contains(index)
+                    && !self.options.is_ignored(cmd)
+                    && is_please_ask(cmd)
+            })
+            .count()
+            .saturating_sub(asked.len());
+        for (index, line) in captured {
             assert!(!line.ends_with("\n"), "unexpected newline at {}", line);
 
+            if self.options.excluded.contains(&index) {
+                continue;
+            }
+
             match line {
-                cmd if IGNORED_COMMANDS.iter().any(|w| cmd.contains(w)) => {
+                cmd if cmd.trim().is_empty() => {
+                    // Blank lines only make diffs noisy
+                }
+                cmd if self.options.is_ignored(&cmd) => {
                     // Ignore these
                 }
+                cmd if is_please_note(&cmd) => res.push(format!("# {}", note_text(&cmd))),
                 cmd if is_please_ask(&cmd) => {
-                    let var = var_iter.next().expect("contains var");
+                    let unrecorded;
+                    let (var, repeated) = match var_iter.next() {
+                        Some(next) => next,
+                        // Building from the latest commands records no variables,
+                        // so the answer is read into a variable of its own
+                        None if self.options.from_last.is_some() => {
+                            unrecorded = Variable {
+                                value: format!("ANSWER_{unnamed}"),
+                                ..Default::default()
+                            };
+                            unnamed = unnamed.saturating_sub(1);
+                            (&unrecorded, false)
+                        }
+                        None => bail!("found an ask prompt with no recorded variable"),
+                    };
+                    if repeated {
+                        res.push(var.expr.clone());
+                        continue;
+                    }
                     // please ask How are you doing? -> read -p "How are you doing?"
-                    let prompt: String = cmd
-                        .split(' ')
-                        .skip_while(|s| !s.starts_with("ask"))
-                        .skip(1)
-                        .collect::<Vec<_>>()
-                        .join(" ")
-                        .trim_matches('\"')
-                        .to_string();
-
-                    let cmd = format!("read -p \"{} \" {}", prompt, var.value);
+                    let prompt = match &var.default {
+                        Some(default) => format!("{} [{}]", ask_prompt(&cmd), default),
+                        None => ask_prompt(&cmd),
+                    };
+                    let cmd = S::prompt(&prompt, &var.value);
 
                     // These need to be in reverse order here
-                    res.push(var.expr.clone());
+                    if !var.expr.is_empty() {
+                        res.push(var.expr.clone());
+                    }
+                    if let Some(validation) = S::validate(&var.value, var.kind) {
+                        res.push(validation);
+                    }
+                    if let Some(default) = &var.default {
+                        res.push(S::default_value(&var.value, default));
+                    }
                     res.push(cmd);
                 }
                 cmd => res.push(cmd),
             }
         }
 
-        res.push("set -e\n".to_string());
-        res.push(SHEBANG.to_string());
+        let mut commands = collapse_cd(res.into_iter().rev(), self.options.drop_cd);
+        if self.options.dedupe {
+            commands.dedup();
+        }
 
-        let correct_order = res.into_iter().rev().collect();
+        let mut correct_order = vec![
+            format!("{}\n", self.options.shebang),
+            S::preamble(&self.options.set_flags),
+        ];
+        correct_order.extend(variables.iter().filter_map(|var| {
+            let value = var.remembered.as_ref()?;
+            Some(S::export(&var.value, value))
+        }));
+        correct_order.extend(commands);
         Ok(correct_order)
     }
+
+    fn last_command(&self, history: String) -> Option<(usize, String)> {
+        captured_commands::<S>(&history, &self.options)
+            .into_iter()
+            .filter(|(index, _)| !self.options.excluded.contains(index))
+            .find(|(_, cmd)| !self.options.is_ignored(cmd) && !is_please_ask(cmd))
+    }
+
+    fn captured_history(&self, history: String) -> Vec<CapturedLine> {
+        let mut lines = captured_commands::<S>(&history, &self.options)
+            .into_iter()
+            .filter(|(_, cmd)| !cmd.trim().is_empty())
+            .map(|(index, command)| CapturedLine {
+                kept: !self.options.excluded.contains(&index) && !self.options.is_ignored(&command),
+                command,
+            })
+            .collect::<Vec<_>>();
+        lines.reverse();
+        lines
+    }
+}
+
+/// Returns the commands after the start of the build from newest to oldest,
+/// each with its index counted from the start of the build
+fn captured_commands<S: Shell>(history: &str, options: &ParseOptions) -> Vec<(usize, String)> {
+    let lines = S::entries(history)
+        .into_iter()
+        .rev()
+        .filter(|line| S::is_command(line))
+        .filter(|line| !(options.skip_failed && S::failed(line)))
+        .map(|line| S::command(&line));
+    let commands = match options.from_last {
+        Some(count) => {
+            // Prompts are kept but only the other commands count
+            let mut commands = vec![];
+            let mut taken = 0;
+            for line in lines.filter(|line| !options.is_ignored(line)) {
+                if taken == count {
+                    break;
+                }
+                if !is_please_ask(&line) {
+                    taken += 1;
+                }
+                commands.push(line);
+            }
+            commands
+        }
+        None => {
+            let mut lines = lines.collect::<Vec<_>>();
+            let (start, end) = marked_region(&lines, options.build_name.as_deref());
+            lines.truncate(start);
+            lines.drain(..end);
+            lines
+        }
+    };
+
+    let len = commands.len();
+    commands
+        .into_iter()
+        .enumerate()
+        .map(|(i, cmd)| (len - 1 - i, cmd))
+        .collect()
+}
+
+/// Returns the range of lines (newest first) to capture as `(start, end)`,
+/// where `start` is the index of the line the capture starts after and `end`
+/// the index of the first captured line.
+///
+/// Normally everything after the start of the build is captured. If the build
+/// has a `please mark-end`, capture stops there and begins after the
+/// `please mark-start` before it, even if that was run before the build
+/// started. A `please mark-start` alone only moves the start.
+fn marked_region(lines: &[String], build_name: Option<&str>) -> (usize, usize) {
+    let build_start = lines
+        .iter()
+        .position(|line| match build_name {
+            Some(name) => started_build(line) == Some(name),
+            None => is_start_of_build(line),
+        })
+        .unwrap_or(lines.len());
+    let is_mark = |cmd: &str, line: &String| line.trim() == cmd;
+    let window = &lines[..build_start];
+
+    if let Some(end) = window.iter().position(|l| is_mark(MARK_END_CMD, l)) {
+        let start = lines[end..]
+            .iter()
+            .position(|l| is_mark(MARK_START_CMD, l))
+            .map_or(build_start, |i| end + i);
+        return (start, end + 1);
+    }
+
+    match window.iter().position(|l| is_mark(MARK_START_CMD, l)) {
+        Some(start) => (start, 0),
+        None => (build_start, 0),
+    }
+}
+
+/// Returns the target of a plain `cd` command, `cd` alone goes home
+/// cd foo -> Some("foo")
+/// cd foo && make -> None
+fn cd_target(cmd: &str) -> Option<&str> {
+    let mut words = cmd.split_whitespace();
+    if words.next()? != "cd" {
+        return None;
+    }
+    let target = words.next().unwrap_or("~");
+    if words.next().is_some() || target.contains(['"', '\'', '\\']) {
+        return None;
+    }
+
+    Some(target)
+}
+
+/// Collapses runs of consecutive `cd` commands into a single `cd` that ends up
+/// in the same directory, so the command after the run still runs where it did.
+/// With `drop_cd` plain `cd` commands are left out entirely.
+fn collapse_cd(commands: impl IntoIterator<Item = String>, drop_cd: bool) -> Vec<String> {
+    let mut res = vec![];
+    let mut run: Vec<String> = vec![];
+
+    for cmd in commands {
+        if cd_target(&cmd).is_some() {
+            if !drop_cd {
+                run.push(cmd);
+            }
+            continue;
+        }
+        res.extend(flush_cd_run(&mut run));
+        res.push(cmd);
+    }
+    res.extend(flush_cd_run(&mut run));
+
+    res
 }
 
-/// Checks if the line is a please ask command
+fn flush_cd_run(run: &mut Vec<String>) -> Vec<String> {
+    let run = std::mem::take(run);
+    if run.len() < 2 {
+        return run;
+    }
+
+    let target =
+        run.iter()
+            .filter_map(|cmd| cd_target(cmd))
+            .try_fold(None::<String>, |dir, target| {
+                // `cd -` depends on the previous directory, so the run cannot be collapsed
+                if target == "-" {
+                    return None;
+                }
+                let is_absolute = ['/', '~', '$'].iter().any(|c| target.starts_with(*c));
+                Some(Some(match dir {
+                    Some(dir) if !is_absolute => {
+                        format!("{}/{}", dir.trim_end_matches('/'), target)
+                    }
+                    _ => target.to_string(),
+                }))
+            });
+
+    match target {
+        Some(Some(target)) => vec![format!("cd {}", target)],
+        _ => run,
+    }
+}
+
+impl ParseOptions {
+    fn is_ignored(&self, cmd: &str) -> bool {
+        cmd.trim_end().ends_with(SKIP_MARKER)
+            || IGNORED_COMMANDS.iter().any(|w| cmd.contains(w))
+            || self
+                .ignored_commands
+                .iter()
+                .any(|w| cmd.contains(w.as_str()))
+    }
+}
+
+/// Checks if the line is a please ask command, optionally run with sudo
 /// please ask "What is your name?" -> true
 /// please ask --help -> false
-/// something else -> false
+/// echo "please ask" -> false
 fn is_please_ask(line: impl AsRef<str>) -> bool {
-    let line = line.as_ref();
-    let is_ask = line.contains("please ask") || line.contains("cargo run -- ask");
-    is_ask && !IGNORED_COMMANDS.contains(&line)
+    let mut words = line
+        .as_ref()
+        .split_whitespace()
+        .skip_while(|word| *word == "sudo");
+    let is_please = match words.next() {
+        Some("please") => true,
+        Some("cargo") => words.next() == Some("run") && words.next() == Some("--"),
+        _ => false,
+    };
+
+    is_please && words.next() == Some("ask") && !matches!(words.next(), Some("--help" | "-h"))
+}
+
+/// Checks if the line is a please note command
+/// please note "Set up the db" -> true
+fn is_please_note(line: &str) -> bool {
+    let mut words = line.split_whitespace();
+    words.next() == Some("please") && words.next() == Some("note")
+}
+
+/// Extracts the text of a please note command
+/// please note "Set up the db" -> Set up the db
+fn note_text(cmd: &str) -> String {
+    let words = cmd.split(' ').skip_while(|s| *s != "note").skip(1);
+    let text = words.collect::<Vec<_>>().join(" ");
+    text.trim().trim_matches('\"').to_string()
+}
+
+/// Options of please ask that take a value
+const ASK_OPTIONS: &[&str] = &["--type", "-t", "--default", "-d", "--build"];
+
+/// Extracts the prompt from a please ask command, skipping its options
+/// please ask --type int "How old are you?" -> How old are you?
+fn ask_prompt(cmd: &str) -> String {
+    let mut words = cmd.split(' ').skip_while(|s| *s != "ask").skip(1);
+    let mut prompt = vec![];
+    while let Some(word) = words.next() {
+        let option = word.split('=').next().unwrap_or_default();
+        if ASK_OPTIONS.contains(&option) {
+            // Skip the value of the option, which may be quoted and contain spaces
+            let mut value = word.split_once('=').map(|(_, value)| value);
+            if value.is_none() {
+                value = words.next();
+            }
+            if let Some(value) = value {
+                if value.starts_with('"') && (value.len() == 1 || !value.ends_with('"')) {
+                    words.by_ref().find(|word| word.ends_with('"'));
+                }
+            }
+            continue;
+        }
+        prompt.push(word);
+    }
+
+    unquote(prompt.join(" ").trim())
+}
+
+/// Removes quotes and escapes the way the shell does, keeping what they quote
+/// "What's your name?" -> What's your name?
+/// Where to? -> Where to?
+fn unquote(text: &str) -> String {
+    let mut res = String::new();
+    let mut quote = None;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(open), c) if c == open => quote = None,
+            // Inside double quotes only these are escaped
+            (Some('"'), '\\') if !matches!(chars.peek(), Some('"' | '\\' | '$' | '`')) => {
+                res.push(c)
+            }
+            (None | Some('"'), '\\') => res.extend(chars.next()),
+            _ => res.push(c),
+        }
+    }
+
+    res
 }
 
 /// Checks if the line is the start of build command
 /// please build "script-name" -> true
 /// please build -> false (finalize cmd)
+/// please build --tag foo -> false (finalize cmd with options)
 fn is_start_of_build(line: impl AsRef<str>) -> bool {
-    let line = line.as_ref();
+    started_build(line.as_ref()).is_some()
+}
+
+/// Returns the name of the script the line starts building
+/// please build "script-name" --tag foo -> Some("script-name")
+/// please build --append script-name -> Some("script-name")
+fn started_build(line: &str) -> Option<&str> {
     if !line.starts_with(BUILD_CMD) {
-        return false;
+        return None;
     }
     // Trim the line and check if it starts with BUILD_CMD
     let remainder = line.trim().trim_start_matches(BUILD_CMD).trim();
 
-    match remainder {
-        "--help" | "-h" => false,
-        _ => !remainder.is_empty(),
+    // The script name comes first, anything else is an option
+    let mut words = remainder.split_whitespace();
+    let name = match words.next()? {
+        "--append" => words.next()?,
+        word => word.strip_prefix("--append=").unwrap_or(word),
+    };
+    if name.starts_with('-') {
+        return None;
     }
+
+    Some(name.trim_matches(['"', '\'']))
+}
+
+/// Format of the history file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryShell {
+    Zsh,
+    Bash,
+    Fish,
+    #[value(name = "powershell")]
+    PowerShell,
 }
 
-pub fn get_parser() -> impl HistoryParser {
-    Parser {
-        shell: std::marker::PhantomData::<Zsh>,
+fn parser<S: Shell + 'static>(options: ParseOptions) -> Box<dyn HistoryParser> {
+    Box::new(Parser {
+        shell: std::marker::PhantomData::<S>,
+        options,
+    })
+}
+
+/// Picks the parser for `options.history_shell`, PowerShell on Windows and zsh elsewhere by default
+pub fn get_parser(options: ParseOptions) -> Box<dyn HistoryParser> {
+    let default = if cfg!(windows) {
+        HistoryShell::PowerShell
+    } else {
+        HistoryShell::Zsh
+    };
+    match options.history_shell.unwrap_or(default) {
+        HistoryShell::Zsh => parser::<Zsh>(options),
+        HistoryShell::Bash => parser::<Bash>(options),
+        HistoryShell::Fish => parser::<Fish>(options),
+        HistoryShell::PowerShell => parser::<PowerShell>(options),
     }
 }
 
@@ -127,15 +790,18 @@ mod should {
         assert!(is_start_of_build("please build \"script-name\"\n"));
         assert!(is_start_of_build("please build ts-jest"));
         assert!(is_start_of_build("please build ts-jest\n"));
+        assert!(is_start_of_build("please build ts-jest --tag js"));
+        assert!(!is_start_of_build("please build --tag js"));
     }
 
     #[test]
     fn parse_zsh_history() {
-        let parser = get_parser();
+        let parser = get_parser(ParseOptions::default());
         let hist = fs::read_to_string("test-data/.zsh_history").unwrap();
         let vars = vec![Variable {
             value: "VAR1".to_string(),
             expr: "echo $VAR1".to_string(),
+            ..Default::default()
         }];
         let res = parser.parse_history(hist, &vars).unwrap();
         assert_eq!(res.len(), 6);
@@ -144,10 +810,11 @@ mod should {
 
     #[test]
     fn parse_zsh_input_thing() {
-        let parser = get_parser();
+        let parser = get_parser(ParseOptions::default());
         let vars = vec![Variable {
             value: "VAR1".to_string(),
             expr: "echo $VAR1".to_string(),
+            ..Default::default()
         }];
         let hist = ": 1713204117:0;please ask \"What is your name?\"".to_string();
         let res = parser.parse_history(hist, &vars).unwrap();
@@ -158,15 +825,17 @@ mod should {
 
     #[test]
     fn use_two_variables() {
-        let parser = get_parser();
+        let parser = get_parser(ParseOptions::default());
         let vars = vec![
             Variable {
                 value: "VAR1".to_string(),
                 expr: "echo $VAR1".to_string(),
+                ..Default::default()
             },
             Variable {
                 value: "VAR2".to_string(),
                 expr: "echo $VAR2".to_string(),
+                ..Default::default()
             },
         ];
 
@@ -178,15 +847,41 @@ mod should {
         assert!(cmd.contains("read -p \"What is your name? \" VAR1"));
     }
 
+    #[test]
+    fn read_repeated_variable_once() {
+        let parser = get_parser(ParseOptions::default());
+        let vars = vec![
+            Variable {
+                value: "NAME".to_string(),
+                expr: "echo $NAME".to_string(),
+                ..Default::default()
+            },
+            Variable {
+                value: "NAME".to_string(),
+                expr: "mkdir $NAME".to_string(),
+                ..Default::default()
+            },
+        ];
+
+        let hist = "please ask \"Name?\"\nls\nplease ask \"Name again?\"".to_string();
+        let res = parser.parse_history(hist, &vars).unwrap();
+
+        assert_eq!(
+            res[2..],
+            ["read -p \"Name? \" NAME", "echo $NAME", "ls", "mkdir $NAME"]
+        );
+    }
+
     #[test]
     fn ignore_things() {
-        let parser = get_parser();
+        let parser = get_parser(ParseOptions::default());
         let vars = vec![];
 
         let hist = fs::read_to_string("test-data/ignored_history").unwrap();
         let res = parser.parse_history(hist, &vars).unwrap();
 
         assert_eq!(res.len(), 3);
+        assert_eq!(res[2], "echo foobar");
     }
 
     macro_rules! ask {
@@ -203,5 +898,578 @@ mod should {
         ask!("please ask \"What is your name?\"");
         ask!(not "please ask --help");
         ask!(not "please ask -h");
+        ask!(not "echo \"please ask\"");
+        ask!(not "pleaseask foo");
+        ask!("please   ask \"q\"");
+        ask!("sudo please ask \"q\"");
+        ask!("cargo run -- ask \"q\"");
+        assert_eq!(ask_prompt("please   ask   \"q\""), "q");
+    }
+
+    #[test]
+    fn use_custom_shebang() {
+        let parser = get_parser(ParseOptions {
+            shebang: "#!/bin/bash".to_string(),
+            ..Default::default()
+        });
+        let hist = ": 1713204117:0;echo foo".to_string();
+        let res = parser.parse_history(hist, &[]).unwrap();
+
+        assert_eq!(res[0], "#!/bin/bash\n");
+    }
+
+    #[test]
+    fn parse_powershell_history() {
+        let parser = Parser {
+            shell: std::marker::PhantomData::<PowerShell>,
+            options: ParseOptions::default(),
+        };
+        let vars = vec![Variable {
+            value: "NAME".to_string(),
+            expr: "echo $NAME".to_string(),
+            ..Default::default()
+        }];
+        let hist =
+            "please build example\nplease ask \"What is your name?\"\nGet-ChildItem\nplease build"
+                .to_string();
+        let res = parser.parse_history(hist, &vars).unwrap();
+
+        assert_eq!(res.len(), 5);
+        assert_eq!(res[1], "$ErrorActionPreference = \"Stop\"\n");
+        assert_eq!(res[2], "$NAME = Read-Host \"What is your name?\"");
+        assert_eq!(res[4], "Get-ChildItem");
+    }
+
+    #[test]
+    fn validate_typed_variables() {
+        let parser = get_parser(ParseOptions::default());
+        let vars = vec![Variable {
+            value: "AGE".to_string(),
+            expr: "echo $AGE".to_string(),
+            kind: VarKind::Int,
+            ..Default::default()
+        }];
+        let hist = ": 1713204117:0;please ask --type int \"How old are you?\"".to_string();
+        let res = parser.parse_history(hist, &vars).unwrap();
+
+        assert_eq!(res.len(), 5);
+        assert_eq!(res[2], "read -p \"How old are you? \" AGE");
+        assert!(res[3].contains("AGE must be a number"));
+        assert_eq!(res[4], "echo $AGE");
+    }
+
+    #[test]
+    fn skip_options_in_prompt() {
+        assert_eq!(ask_prompt("please ask \"Name?\""), "Name?");
+        assert_eq!(ask_prompt("please ask --type int \"Age?\""), "Age?");
+        assert_eq!(ask_prompt("please ask --type=path Where to?"), "Where to?");
+        assert_eq!(
+            ask_prompt("please ask --default \"John Doe\" \"Name?\""),
+            "Name?"
+        );
+    }
+
+    #[test]
+    fn extract_quoted_and_unquoted_prompts() {
+        assert_eq!(
+            ask_prompt("please ask \"What is your name?\""),
+            "What is your name?"
+        );
+        assert_eq!(
+            ask_prompt("please ask What is your name?"),
+            "What is your name?"
+        );
+        assert_eq!(ask_prompt("please ask Name?"), "Name?");
+        assert_eq!(ask_prompt("please ask 'Say \"hi\"'"), "Say \"hi\"");
+        assert_eq!(ask_prompt("please ask \"What's up?\""), "What's up?");
+        assert_eq!(ask_prompt("please ask What\\'s up?"), "What's up?");
+        assert_eq!(
+            ask_prompt("please ask \"Dir  (\\$HOME)?\""),
+            "Dir  ($HOME)?"
+        );
+
+        // Quotes left in the prompt don't end the string in the script
+        assert_eq!(
+            Zsh::prompt("Say \"hi\" to $USER", "X"),
+            "read -p \"Say \\\"hi\\\" to \\$USER \" X"
+        );
+    }
+
+    #[test]
+    fn use_default_value() {
+        let parser = get_parser(ParseOptions::default());
+        let vars = vec![Variable {
+            value: "NAME".to_string(),
+            expr: "echo $NAME".to_string(),
+            default: Some("it's me".to_string()),
+            ..Default::default()
+        }];
+        let hist = ": 1713204117:0;please ask --default \"it's me\" \"Who are you?\"".to_string();
+        let res = parser.parse_history(hist, &vars).unwrap();
+
+        assert_eq!(res.len(), 5);
+        assert_eq!(res[2], "read -p \"Who are you? [it's me] \" NAME");
+        assert_eq!(res[3], "NAME=${NAME:-'it'\\''s me'}");
+    }
+
+    #[test]
+    fn exclude_undone_commands() {
+        let hist = fs::read_to_string("test-data/.zsh_history").unwrap();
+        let vars = vec![Variable {
+            value: "VAR1".to_string(),
+            expr: "echo $VAR1".to_string(),
+            ..Default::default()
+        }];
+
+        let parser = get_parser(ParseOptions::default());
+        let (index, cmd) = parser.last_command(hist.clone()).unwrap();
+        assert_eq!(cmd, "echo foobar");
+        assert_eq!(index, 2);
+
+        let parser = get_parser(ParseOptions {
+            excluded: vec![index],
+            ..Default::default()
+        });
+        let (_, cmd) = parser.last_command(hist.clone()).unwrap();
+        assert_eq!(cmd, "ls");
+
+        let res = parser.parse_history(hist, &vars).unwrap();
+        assert_eq!(res.len(), 5);
+        assert!(!res.contains(&"echo foobar".to_string()));
+    }
+
+    #[test]
+    fn use_custom_set_flags() {
+        let parser = get_parser(ParseOptions {
+            set_flags: "-euo pipefail".to_string(),
+            ..Default::default()
+        });
+        let hist = ": 1713204117:0;echo foo".to_string();
+        let res = parser.parse_history(hist, &[]).unwrap();
+
+        assert_eq!(res[1], "set -euo pipefail\n");
+    }
+
+    fn commands(cmds: &[&str]) -> Vec<String> {
+        cmds.iter().map(|cmd| cmd.to_string()).collect()
+    }
+
+    #[test]
+    fn collapse_consecutive_cd() {
+        let res = collapse_cd(
+            commands(&[
+                "cd /tmp",
+                "cd foo",
+                "cd ../bar",
+                "make",
+                "cd ~/src",
+                "cd app",
+                "ls",
+            ]),
+            false,
+        );
+        assert_eq!(
+            res,
+            vec!["cd /tmp/foo/../bar", "make", "cd ~/src/app", "ls"]
+        );
+
+        let res = collapse_cd(commands(&["cd foo", "cd /etc", "cat hosts"]), false);
+        assert_eq!(res, vec!["cd /etc", "cat hosts"]);
+
+        // A single cd is kept as is
+        let res = collapse_cd(commands(&["ls", "cd", "ls"]), false);
+        assert_eq!(res, vec!["ls", "cd", "ls"]);
+
+        // cd - depends on the previous directory
+        let res = collapse_cd(commands(&["cd foo", "cd -", "ls"]), false);
+        assert_eq!(res, vec!["cd foo", "cd -", "ls"]);
+
+        // Not plain cd commands
+        let res = collapse_cd(commands(&["cd foo && make", "cd bar", "ls"]), false);
+        assert_eq!(res, vec!["cd foo && make", "cd bar", "ls"]);
+    }
+
+    #[test]
+    fn drop_cd_commands() {
+        let res = collapse_cd(
+            commands(&["cd /tmp", "ls", "cd foo", "cd bar", "make"]),
+            true,
+        );
+        assert_eq!(res, vec!["ls", "make"]);
+    }
+
+    #[test]
+    fn ignore_custom_commands() {
+        let parser = get_parser(ParseOptions {
+            ignored_commands: vec!["pls".to_string(), "htop".to_string()],
+            ..Default::default()
+        });
+        let hist = ": 1713204117:0;pls list\n: 1713204117:0;ls\n: 1713204117:0;htop".to_string();
+        let res = parser.parse_history(hist, &[]).unwrap();
+
+        assert_eq!(res.len(), 3);
+        assert_eq!(res[2], "ls");
+    }
+
+    #[test]
+    fn skip_marked_commands() {
+        let parser = get_parser(ParseOptions::default());
+        let hist = ": 1713204117:0;please build foo\n: 1713204117:0;ls\n: 1713204117:0;cat secrets # please:skip\n: 1713204117:0;please skip\n: 1713204117:0;pwd".to_string();
+        let res = parser.parse_history(hist, &[]).unwrap();
+
+        assert_eq!(res[2..], ["ls", "pwd"]);
+    }
+
+    #[test]
+    fn skip_failed_commands() {
+        /// History with the exit code in front of each command
+        struct Annotated;
+
+        impl Shell for Annotated {
+            fn command(line: &str) -> String {
+                line.split_once(' ').unwrap().1.to_string()
+            }
+
+            fn failed(line: &str) -> bool {
+                !line.starts_with("0 ")
+            }
+        }
+
+        let hist = "0 please build foo\n0 ls\n1 cat missing\n0 pwd".to_string();
+        let res = parser::<Annotated>(ParseOptions::default())
+            .parse_history(hist.clone(), &[])
+            .unwrap();
+        assert_eq!(res[2..], ["ls", "cat missing", "pwd"]);
+
+        let res = parser::<Annotated>(ParseOptions {
+            skip_failed: true,
+            ..Default::default()
+        })
+        .parse_history(hist, &[])
+        .unwrap();
+        assert_eq!(res[2..], ["ls", "pwd"]);
+    }
+
+    #[test]
+    fn turn_notes_into_comments() {
+        let parser = get_parser(ParseOptions::default());
+        let hist = [
+            "please build foo",
+            "please note \"Set up the db\"",
+            "createdb foo",
+            "please note run it",
+            "echo please note",
+        ]
+        .join("\n");
+        let res = parser.parse_history(hist, &[]).unwrap();
+
+        assert_eq!(
+            res[2..],
+            [
+                "# Set up the db",
+                "createdb foo",
+                "# run it",
+                "echo please note"
+            ]
+        );
+    }
+
+    #[test]
+    fn keep_multi_line_commands() {
+        let parser = get_parser(ParseOptions::default());
+        let hist = [
+            ": 1713204117:0;please build greet",
+            ": 1713204120:0;cat <<EOF > greeting.txt\\",
+            "hello\\",
+            "EOF",
+            ": 1713204125:0;for f in *.txt; do\\",
+            "  wc -l $f\\",
+            "done",
+            ": 1713204130:0;ls",
+        ]
+        .join("\n");
+        let res = parser.parse_history(hist, &[]).unwrap();
+
+        assert_eq!(
+            res[2..],
+            [
+                "cat <<EOF > greeting.txt\nhello\nEOF",
+                "for f in *.txt; do\n  wc -l $f\ndone",
+                "ls"
+            ]
+        );
+    }
+
+    #[test]
+    fn error_on_ask_without_variable() {
+        let parser = get_parser(ParseOptions::default());
+        let hist =
+            ": 1713204117:0;please build foo\n: 1713204117:0;please ask \"Name?\"".to_string();
+        let err = parser.parse_history(hist, &[]).unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "found an ask prompt with no recorded variable"
+        );
+    }
+
+    #[test]
+    fn keep_semicolons_in_commands() {
+        let parser = get_parser(ParseOptions::default());
+        let hist = ": 1713204117:0;please build foo\n: 1713204117:0;for x in a b; do echo $x; done"
+            .to_string();
+        let res = parser.parse_history(hist, &[]).unwrap();
+
+        assert_eq!(res.len(), 3);
+        assert_eq!(res[2], "for x in a b; do echo $x; done");
+    }
+
+    #[test]
+    fn parse_both_zsh_formats() {
+        assert_eq!(Zsh::command(": 1713204117:0;ls -la"), "ls -la");
+        assert_eq!(Zsh::command("ls -la"), "ls -la");
+        assert_eq!(Zsh::command("echo a; echo b"), "echo a; echo b");
+        assert_eq!(Zsh::command(": not:metadata;ls"), ": not:metadata;ls");
+
+        let parser = get_parser(ParseOptions::default());
+        let hist = "please build foo\nls\necho a; echo b".to_string();
+        let res = parser.parse_history(hist, &[]).unwrap();
+        assert_eq!(res[2..], ["ls", "echo a; echo b"]);
+    }
+
+    #[test]
+    fn export_remembered_variables() {
+        let parser = get_parser(ParseOptions::default());
+        let vars = vec![
+            Variable {
+                value: "TOKEN".to_string(),
+                remembered: Some("it's secret".to_string()),
+                ..Default::default()
+            },
+            Variable {
+                value: "NAME".to_string(),
+                expr: "echo $NAME".to_string(),
+                ..Default::default()
+            },
+        ];
+        let hist = ": 1713204117:0;please remember TOKEN\n: 1713204117:0;please ask \"Name?\"\n: 1713204117:0;curl -H $TOKEN".to_string();
+        let res = parser.parse_history(hist, &vars).unwrap();
+
+        assert_eq!(res[2], "export TOKEN='it'\\''s secret'");
+        assert_eq!(res[3], "read -p \"Name? \" NAME");
+        assert_eq!(res[4], "echo $NAME");
+        assert_eq!(res[5], "curl -H $TOKEN");
+    }
+
+    #[test]
+    fn capture_from_own_build_start() {
+        let parser = get_parser(ParseOptions {
+            build_name: Some("first".to_string()),
+            ..Default::default()
+        });
+        let hist = [
+            "please build first",
+            "ls",
+            "please build second",
+            "echo hi",
+            "please build --build first",
+        ]
+        .join("\n");
+        let res = parser.parse_history(hist, &[]).unwrap();
+
+        assert_eq!(res[2..], ["ls", "echo hi"]);
+        assert_eq!(started_build("please build \"foo\" --tag x"), Some("foo"));
+        assert_eq!(started_build("please build --build foo"), None);
+        assert_eq!(started_build("please build --append foo"), Some("foo"));
+        assert_eq!(started_build("please build --append=foo"), Some("foo"));
+    }
+
+    #[test]
+    fn capture_between_marks() {
+        let parser = get_parser(ParseOptions::default());
+        let capture =
+            |hist: &[&str]| parser.parse_history(hist.join("\n"), &[]).unwrap()[2..].to_vec();
+
+        // Marks around the build start
+        let hist = [
+            "please mark-start",
+            "cd src",
+            "please build foo",
+            "make",
+            "please mark-end",
+            "ls",
+        ];
+        assert_eq!(capture(&hist), ["cd src", "make"]);
+
+        // Only a start mark
+        let hist = ["please build foo", "ls", "please mark-start", "make"];
+        assert_eq!(capture(&hist), ["make"]);
+
+        // An end mark without a start captures from the build start
+        let hist = ["please build foo", "ls", "please mark-end", "make"];
+        assert_eq!(capture(&hist), ["ls"]);
+
+        // Marks from before the build are left alone
+        let hist = [
+            "please mark-start",
+            "please mark-end",
+            "please build foo",
+            "ls",
+        ];
+        assert_eq!(capture(&hist), ["ls"]);
+    }
+
+    #[test]
+    fn give_captured_history() {
+        let parser = get_parser(ParseOptions {
+            excluded: vec![1],
+            ..Default::default()
+        });
+        let hist = [
+            "ls",
+            "please build foo",
+            "pwd",
+            "rm -rf x",
+            "please list",
+            "please ask Name?",
+        ];
+        let res = parser.captured_history(hist.join("\n"));
+
+        let line = |command: &str, kept| CapturedLine {
+            command: command.to_string(),
+            kept,
+        };
+        assert_eq!(
+            res,
+            [
+                line("pwd", true),
+                line("rm -rf x", false),
+                line("please list", false),
+                line("please ask Name?", true)
+            ]
+        );
+    }
+
+    #[test]
+    fn capture_latest_commands() {
+        let parser = get_parser(ParseOptions {
+            from_last: Some(2),
+            ..Default::default()
+        });
+        let hist = ["ls", "please build foo", "pwd", "please list", "echo hi"].join("\n");
+        let res = parser.parse_history(hist.clone(), &[]).unwrap();
+        assert_eq!(res[2..], ["pwd", "echo hi"]);
+
+        let parser = get_parser(ParseOptions {
+            from_last: Some(10),
+            ..Default::default()
+        });
+        let res = parser.parse_history(hist, &[]).unwrap();
+        assert_eq!(res[2..], ["ls", "pwd", "echo hi"]);
+
+        // Prompts become prompts but don't count
+        let parser = get_parser(ParseOptions {
+            from_last: Some(2),
+            ..Default::default()
+        });
+        let hist = [
+            "please ask \"Too old?\"",
+            "ls",
+            "pwd",
+            "please ask \"Name?\"",
+            "please ask \"Age?\"",
+            "echo hi",
+        ]
+        .join("\n");
+        let res = parser.parse_history(hist, &[]).unwrap();
+        assert_eq!(
+            res[2..],
+            [
+                "pwd",
+                "read -p \"Name? \" ANSWER_1",
+                "read -p \"Age? \" ANSWER_2",
+                "echo hi"
+            ]
+        );
+    }
+
+    #[test]
+    fn skip_blank_lines() {
+        let parser = get_parser(ParseOptions::default());
+        let hist = ": 1713204117:0;please build foo\n\n: 1713204117:0;ls\n: 1713204117:0;   \n\n"
+            .to_string();
+        let res = parser.parse_history(hist, &[]).unwrap();
+
+        assert_eq!(res[2..], ["ls"]);
+    }
+
+    #[test]
+    fn dedupe_repeated_commands() {
+        let hist = "please build foo\necho hi\necho hi\nls\necho hi".to_string();
+
+        let parser = get_parser(ParseOptions {
+            dedupe: true,
+            ..Default::default()
+        });
+        let res = parser.parse_history(hist.clone(), &[]).unwrap();
+        assert_eq!(res[2..], ["echo hi", "ls", "echo hi"]);
+
+        let parser = get_parser(ParseOptions::default());
+        let res = parser.parse_history(hist, &[]).unwrap();
+        assert_eq!(res[2..], ["echo hi", "echo hi", "ls", "echo hi"]);
+    }
+
+    #[test]
+    fn skip_bash_timestamps() {
+        let bash = get_parser(ParseOptions {
+            history_shell: Some(HistoryShell::Bash),
+            ..Default::default()
+        });
+        let hist = [
+            "#1713204117",
+            "please build foo",
+            "#1713204118",
+            "ls",
+            "#1713204119",
+            "#1713204120",
+            "echo '#1713204121'",
+            "#",
+            "#1713204122",
+        ]
+        .join("\n");
+
+        let res = bash.parse_history(hist.clone(), &[]).unwrap();
+        assert_eq!(res[2..], ["ls", "echo '#1713204121'", "#"]);
+        assert_eq!(bash.last_command(hist.clone()), Some((2, "#".to_string())));
+        let captured = bash.captured_history(hist);
+        assert!(captured.iter().all(|line| !line.command.starts_with("#17")));
+    }
+
+    #[test]
+    fn parse_bash_and_fish_history() {
+        let bash = get_parser(ParseOptions {
+            history_shell: Some(HistoryShell::Bash),
+            ..Default::default()
+        });
+        let hist = "#1713204117\nplease build foo\n#1713204118\nls\n# a comment\n".to_string();
+        let res = bash.parse_history(hist, &[]).unwrap();
+        assert_eq!(res[2..], ["ls", "# a comment"]);
+
+        let fish = get_parser(ParseOptions {
+            history_shell: Some(HistoryShell::Fish),
+            ..Default::default()
+        });
+        let hist = [
+            "- cmd: please build foo",
+            "  when: 1713204117",
+            "- cmd: cd /tmp",
+            "  when: 1713204118",
+            "  paths:",
+            "    - /tmp",
+            "- cmd: echo a\\\\b",
+        ]
+        .join("\n");
+        let res = fish.parse_history(hist, &[]).unwrap();
+        assert_eq!(res[2..], ["cd /tmp", "echo a\\b"]);
     }
 }
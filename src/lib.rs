@@ -0,0 +1,20 @@
+//! Turns shell history into reusable scripts.
+//!
+//! The `please` binary is a thin command line on top of this crate,
+//! other programs can use the same scripts, builds and history parsing:
+//!
+//! ```no_run
+//! let config = please::Config::load().unwrap();
+//! for script in please::get_scripts(&config).unwrap() {
+//!     println!("{}", script.script_name());
+//! }
+//! ```
+
+pub mod config;
+pub mod highlight;
+pub mod history_parser;
+pub mod script;
+
+pub use config::Config;
+pub use history_parser::{get_parser, CapturedLine, HistoryParser, HistoryShell, ParseOptions};
+pub use script::{get_scripts, Script, ScriptBuilder};
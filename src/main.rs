@@ -1,107 +1,828 @@
-use crate::{config::Config, script::get_scripts};
-use anyhow::Context;
-use clap::{command, Parser, Subcommand};
-use dialoguer::Input;
+use anyhow::{ensure, Context};
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::{ArgValueCandidates, CompleteEnv, CompletionCandidate};
+use dialoguer::{Confirm, FuzzySelect, Input, Select};
 use owo_colors::{OwoColorize, Style};
-use script::{Script, ScriptBuilder};
+use please::{
+    config::Config,
+    highlight::highlight_sh,
+    history_parser::HistoryShell,
+    script::{
+        self, build_statuses, clean_builds, doctor, get_scripts, group_by_tag, import_script,
+        log_run, read_run_log, run_outcome, script_names, search_scripts, sort_scripts, stats,
+        RunOptions, Script, ScriptBuilder, ScriptFailed, ScriptTimedOut, SortBy, VarKind, Variable,
+        TIMEOUT_EXIT_CODE,
+    },
+};
+use std::{
+    env,
+    ffi::OsString,
+    io::{self, IsTerminal},
+    path::PathBuf,
+    process,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
 
-mod config;
-mod history_parser;
-mod script;
+/// Prints the friendly messages around the actual output, unless `--quiet` is set
+macro_rules! say {
+    ($($arg:tt)*) => {
+        if !QUIET.load(Ordering::Relaxed) {
+            println!($($arg)*);
+        }
+    };
+}
 
 // TODO: Add a comment to get some info about the script
-// TODO: COLORIZE Maybe
 
-fn main() -> anyhow::Result<()> {
+fn main() {
+    CompleteEnv::with_factory(Args::command).complete();
+
+    if let Err(err) = try_main() {
+        eprintln!("Error: {:?}", err);
+        // Failed scripts make please exit with the same code
+        let code = err
+            .downcast_ref::<ScriptFailed>()
+            .and_then(|failed| failed.code)
+            .or_else(|| {
+                err.downcast_ref::<ScriptTimedOut>()
+                    .map(|_| TIMEOUT_EXIT_CODE)
+            })
+            .unwrap_or(1);
+        process::exit(code);
+    }
+}
+
+fn try_main() -> anyhow::Result<()> {
     let args = Args::parse();
-    let purpel = Style::new().purple();
+    let no_color = env::var_os("NO_COLOR");
+    COLOR.store(
+        use_color(args.no_color, no_color, io::stdout().is_terminal()),
+        Ordering::Relaxed,
+    );
+    QUIET.store(args.quiet, Ordering::Relaxed);
+
+    ensure!(
+        args.script.is_none() || args.command.is_none(),
+        "Give either a script or a command, use `please run <script>` for scripts named like commands"
+    );
 
+    let config = load_config(args.global)?;
     if let Some(script) = args.script {
-        println!("Okey, running `{}` for you!", script.style(purpel));
-        parse_and_run(script)?
+        let options = run_options(args.cwd, args.timeout, args.env);
+        let repeat = Repeat {
+            times: args.repeat,
+            keep_going: args.keep_going,
+        };
+        parse_and_run(&config, script, args.dry_run, options, repeat)?
     } else {
         let cmd = args.command.expect("should have a command");
-        cmd.run()?;
+        cmd.run(&config)?;
     };
 
     Ok(())
 }
 
-fn parse_and_run(script: String) -> anyhow::Result<()> {
-    let script: Script = script.parse().context("parse script")?;
-    script.run()
+/// How many times `please run` runs the script, see `--repeat`
+struct Repeat {
+    times: u32,
+    /// Run the rest of the times after a failed run
+    keep_going: bool,
+}
+
+fn parse_and_run(
+    config: &Config,
+    script: String,
+    dry_run: bool,
+    options: RunOptions,
+    repeat: Repeat,
+) -> anyhow::Result<()> {
+    let purpel = highlight();
+    let parsed = Script::new(config, &script).context("parse script")?;
+
+    if dry_run {
+        let contents = parsed.contents()?;
+        say!("This is what `{}` would run: ^^\n", script.style(purpel));
+        print_numbered(&contents);
+        return Ok(());
+    }
+
+    say!("Okey, running `{}` for you!", script.style(purpel));
+    let mut result = Ok(());
+    let mut failed = 0;
+    for run in 1..=repeat.times {
+        let run_result = parsed.run_with(&options);
+        if config.run_log {
+            if let Err(err) = log_run(config, &parsed, &run_result) {
+                eprintln!("Warning: {err:#}");
+            }
+        }
+        if repeat.times > 1 {
+            say!("Run {}/{}: {}", run, repeat.times, run_outcome(&run_result));
+        }
+        if run_result.is_err() {
+            failed += 1;
+            result = run_result;
+            if !repeat.keep_going {
+                if run < repeat.times {
+                    say!("Stopping after the failed run, pass `--keep-going` to run the rest");
+                }
+                break;
+            }
+        }
+    }
+    if repeat.keep_going && failed > 0 {
+        say!("{} of {} runs failed", failed, repeat.times);
+    }
+
+    // With `--keep-going` please exits like the last failed run
+    result
+}
+
+fn run_options(
+    cwd: Option<PathBuf>,
+    timeout: Option<u64>,
+    env: Vec<(String, String)>,
+) -> RunOptions {
+    RunOptions {
+        cwd,
+        timeout: timeout.filter(|&secs| secs > 0).map(Duration::from_secs),
+        env,
+    }
+}
+
+/// Parses a `KEY=VALUE` given to `--env`, the value may be empty or contain `=`
+fn parse_env_var(var: &str) -> anyhow::Result<(String, String)> {
+    let (key, value) = var
+        .split_once('=')
+        .with_context(|| format!("Expected `KEY=VALUE`, got `{var}`"))?;
+    ensure!(
+        !key.is_empty() && !key.contains(char::is_whitespace),
+        "Invalid variable name `{key}` in `{var}`"
+    );
+
+    Ok((key.to_string(), value.to_string()))
+}
+
+fn print_scripts(scripts: &[Script]) {
+    let purpel = highlight();
+    for script in scripts {
+        println!("\t{}", script.script_name().style(purpel));
+    }
+}
+
+fn print_listing(scripts: &mut [Script], long: bool, sort: SortBy) -> anyhow::Result<()> {
+    if !long {
+        print_scripts(scripts);
+        return Ok(());
+    }
+    sort_scripts(scripts, sort);
+    print_scripts_long(scripts)
+}
+
+/// Prints the scripts indented under each of their tags, the untagged ones last
+fn print_tree(scripts: &[Script]) {
+    let purpel = highlight();
+    for (tag, scripts) in group_by_tag(scripts) {
+        println!("{}", tag.as_deref().unwrap_or("untagged"));
+        for script in scripts {
+            println!("\t{}", script.script_name().style(purpel));
+        }
+    }
+}
+
+/// Prints the scripts with their size in bytes and when they were last modified
+fn print_scripts_long(scripts: &[Script]) -> anyhow::Result<()> {
+    let purpel = highlight();
+    let width = scripts
+        .iter()
+        .map(|script| script.script_name().len())
+        .max()
+        .unwrap_or_default();
+    for script in scripts {
+        let metadata = script.metadata()?;
+        let modified = metadata.modified().context("read modified time")?;
+        let name = format!("{:<width$}", script.script_name(), width = width);
+        println!(
+            "\t{}  {:>8}  {}",
+            name.style(purpel),
+            metadata.len(),
+            humantime::format_rfc3339_seconds(modified)
+        );
+    }
+
+    Ok(())
+}
+
+fn print_variables(variables: &[Variable]) {
+    if variables.is_empty() {
+        say!("\nNo variables yet, add one with `please ask` or `please remember`");
+        return;
+    }
+    say!("\nVariables: ^^");
+    let purpel = highlight();
+    for (name, rest) in variable_rows(variables) {
+        println!("\t{}  {}", name.style(purpel), rest);
+    }
+}
+
+/// Variable names padded to the same width next to their expression
+/// or remembered value
+fn variable_rows(variables: &[Variable]) -> Vec<(String, String)> {
+    let width = variables
+        .iter()
+        .map(|var| var.value.len())
+        .max()
+        .unwrap_or_default();
+    variables
+        .iter()
+        .map(|var| {
+            let name = format!("{:<width$}", var.value, width = width);
+            let rest = match &var.remembered {
+                Some(value) => format!("remembered as `{value}`"),
+                None => var.expr.clone(),
+            };
+            (name, rest)
+        })
+        .collect()
+}
+
+static COLOR: AtomicBool = AtomicBool::new(true);
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Colors are used unless disabled with `--no-color`, a non-empty
+/// `NO_COLOR` or when stdout is not a terminal
+fn use_color(no_color_flag: bool, no_color_env: Option<OsString>, is_tty: bool) -> bool {
+    let no_color_env = no_color_env.is_some_and(|value| !value.is_empty());
+    !no_color_flag && !no_color_env && is_tty
+}
+
+/// Style used for script names and such, plain when colors are disabled
+fn highlight() -> Style {
+    if COLOR.load(Ordering::Relaxed) {
+        Style::new().purple()
+    } else {
+        Style::new()
+    }
+}
+
+/// Green check for passed checks and a red cross for failed ones
+fn check_mark(ok: bool) -> String {
+    let (mark, style) = if ok {
+        ("✓", Style::new().green())
+    } else {
+        ("✗", Style::new().red())
+    };
+    if COLOR.load(Ordering::Relaxed) {
+        mark.style(style).to_string()
+    } else {
+        mark.to_string()
+    }
+}
+
+fn load_config(global: bool) -> anyhow::Result<Config> {
+    if global {
+        Config::load_global()
+    } else {
+        Config::load()
+    }
+}
+
+/// Config for completions, the command line being completed is
+/// in the arguments so `--global` is looked for there
+fn completion_config() -> anyhow::Result<Config> {
+    load_config(env::args_os().any(|arg| arg == "--global"))
+}
+
+/// Completes script names from the scripts dir at completion time
+fn script_candidates() -> Vec<CompletionCandidate> {
+    let Ok(config) = completion_config() else {
+        return vec![];
+    };
+    script_names(&config)
+        .into_iter()
+        .map(CompletionCandidate::new)
+        .collect()
+}
+
+/// Completes the names of the builds in progress
+fn build_candidates() -> Vec<CompletionCandidate> {
+    let Ok(config) = completion_config() else {
+        return vec![];
+    };
+    config
+        .active_builds()
+        .into_iter()
+        .map(CompletionCandidate::new)
+        .collect()
+}
+
+/// Prints the given text with 1-based line numbers in front of each line
+fn print_numbered(text: &str) {
+    let lines: Vec<&str> = text.lines().collect();
+    let width = lines.len().to_string().len();
+    for (i, line) in lines.iter().enumerate() {
+        println!("{:>width$} | {}", i + 1, line, width = width);
+    }
 }
 
 impl Command {
-    fn run(self) -> anyhow::Result<()> {
-        let purpel = Style::new().purple();
+    fn run(self, config: &Config) -> anyhow::Result<()> {
+        let purpel = highlight();
 
         match self {
-            Command::Run { script } => {
-                println!("Okey, running `{}` for you!", script.style(purpel));
-                parse_and_run(script)?
-            }
-            Command::Build { script } => {
-                if let Some(script) = script {
-                    let builder = ScriptBuilder::build_new(&script);
-                    builder.start_build()?;
-                    println!("Started building script `{}` ^^", script.style(purpel));
+            Command::Run {
+                script,
+                dry_run,
+                cwd,
+                timeout,
+                env,
+                repeat,
+                keep_going,
+            } => {
+                let script = match script {
+                    Some(script) => script,
+                    None => pick_script(config)?,
+                };
+                let repeat = Repeat {
+                    times: repeat,
+                    keep_going,
+                };
+                let options = run_options(cwd, timeout, env);
+                parse_and_run(config, script, dry_run, options, repeat)?
+            }
+            Command::Build {
+                script,
+                tag,
+                shell,
+                shell_aware,
+                force,
+                yes,
+                edit,
+                interactive,
+                name,
+                output,
+                append,
+                from,
+                histfile,
+                history_shell,
+                target,
+            } => {
+                let configure = |builder: &mut ScriptBuilder| {
+                    builder.add_tags(tag);
+                    if let Some(shell) = shell {
+                        builder.set_shell(shell);
+                    }
+                    if shell_aware {
+                        builder.set_shell_aware();
+                    }
+                    if let Some(histfile) = histfile {
+                        builder.set_histfile(histfile);
+                    }
+                    if let Some(history_shell) = history_shell {
+                        builder.set_history_shell(history_shell);
+                    }
+                };
+
+                if edit {
+                    let mut builder = ScriptBuilder::load_current(config, target.name())?;
+                    if builder.edit()? {
+                        builder.save_replace()?;
+                        say!("Saved changes to the current build");
+                    }
+                } else if let Some(script) = script {
+                    let mut builder = ScriptBuilder::build_new(config, &script)?;
+                    configure(&mut builder);
+                    if let Some(count) = from {
+                        if !force {
+                            Script::new(config, &script)?.ensure_not_exists()?;
+                        }
+                        builder.build_from_last(count, force)?;
+                        say!("Built script `{}`", script.style(purpel));
+                        return Ok(());
+                    }
+                    if start_build(&builder, force, yes)? {
+                        say!("Started building script `{}` ^^", script.style(purpel));
+                    }
+                } else if let Some(script) = append {
+                    let mut builder = ScriptBuilder::build_append(config, &script)?;
+                    configure(&mut builder);
+                    if start_build(&builder, force, yes)? {
+                        say!("Started adding to script `{}` ^^", script.style(purpel));
+                    }
                 } else {
-                    let builder = ScriptBuilder::load_current()?;
+                    let mut builder = ScriptBuilder::load_current(config, target.name())?;
+                    configure(&mut builder);
+                    if let Some(name) = name {
+                        builder.set_name(name)?;
+                    }
+                    if let Some(output) = &output {
+                        builder.set_output(output)?;
+                    }
                     let name = builder.get_script_name();
-                    builder.build()?;
-                    println!("Built script `{}`", name.style(purpel));
+                    if interactive {
+                        if io::stdin().is_terminal() {
+                            builder.review_lines(review_line)?;
+                        } else {
+                            eprintln!("Warning: no terminal to review in, keeping every command");
+                        }
+                    }
+                    if !force && builder.is_empty()? {
+                        ensure!(
+                            io::stdin().is_terminal(),
+                            "No commands were captured, pass `--force` to build an empty script"
+                        );
+                        eprintln!("Warning: No commands were captured");
+                        if !confirm("Build the script anyway?")? {
+                            say!("Okey, the build is still in progress ^^");
+                            return Ok(());
+                        }
+                    }
+                    let path = builder.output_path();
+                    builder.build(force)?;
+                    if output.is_some() {
+                        say!(
+                            "Built script `{}` in {}",
+                            name.style(purpel),
+                            path.display()
+                        );
+                    } else {
+                        say!("Built script `{}`", name.style(purpel));
+                    }
                 }
             }
-            Command::List => {
-                let scripts = get_scripts(Config::default())?;
+            Command::List {
+                tag,
+                json,
+                long,
+                sort,
+                path,
+                tree,
+            } => {
+                let mut scripts = get_scripts(config)?;
+                if let Some(tag) = &tag {
+                    scripts.retain(|script| script.tags().contains(tag));
+                }
+                if path {
+                    for script in &scripts {
+                        println!("{}", script.path().display());
+                    }
+                    return Ok(());
+                }
+                if tree {
+                    print_tree(&scripts);
+                    return Ok(());
+                }
+                if json {
+                    let infos = scripts.iter().map(Script::info).collect::<Vec<_>>();
+                    let json = serde_json::to_string_pretty(&infos).context("serialize scripts")?;
+                    println!("{}", json);
+                    return Ok(());
+                }
+                if let Some(tag) = tag {
+                    if scripts.is_empty() {
+                        say!("No scripts tagged `{}`", tag.style(purpel));
+                        return Ok(());
+                    }
+                    say!("Here are your scripts tagged `{}`: ^^", tag.style(purpel));
+                    print_listing(&mut scripts, long, sort)?;
+                    return Ok(());
+                }
                 if scripts.is_empty() {
-                    println!("Looks like you don't have any scripts yet!");
-                    println!("You can start creating one with `please create <script name>` ^^");
+                    say!("Looks like you don't have any scripts yet!");
+                    say!("You can start creating one with `please create <script name>` ^^");
                     return Ok(());
                 }
-                println!("Here are your scripts: ^^");
-                for script in scripts {
-                    println!("\t{}", script.script_name().style(purpel));
+                say!("Here are your scripts: ^^");
+                print_listing(&mut scripts, long, sort)?;
+            }
+            Command::Last { name, count, force } => {
+                let name = match name {
+                    Some(name) => name,
+                    None => ask_script_name()?,
+                };
+                if !force {
+                    Script::new(config, &name)?.ensure_not_exists()?;
                 }
+                ScriptBuilder::build_new(config, &name)?.build_from_last(count, force)?;
+                say!(
+                    "Built script `{}` from your last {} commands ^^",
+                    name.style(purpel),
+                    count
+                );
             }
-            Command::Current => {
-                let builder = ScriptBuilder::load_current()?;
-                println!("This is what your current script looks like: ^^\n");
-                builder.display_script()?;
+            Command::Search { query } => {
+                let scripts = search_scripts(get_scripts(config)?, &query);
+                if scripts.is_empty() {
+                    say!("No scripts matching `{}`", query.style(purpel));
+                    return Ok(());
+                }
+                say!(
+                    "Here are the scripts matching `{}`: ^^",
+                    query.style(purpel)
+                );
+                print_scripts(&scripts);
             }
-            Command::Edit { script } => {
-                let script: Script = script.parse().context("parse script")?;
-                script.edit()
+            Command::Current {
+                numbered,
+                all,
+                target,
+            } => {
+                let builder = ScriptBuilder::load_current(config, target.name())?;
+                say!("This is what your current script looks like: ^^\n");
+                builder.display_script(numbered, COLOR.load(Ordering::Relaxed))?;
+                if all {
+                    print_variables(builder.variables());
+                }
             }
-            Command::Reset => {
-                let builder = ScriptBuilder::load_current()?;
+            Command::History { target } => {
+                let builder = ScriptBuilder::load_current(config, target.name())?;
+                let color = COLOR.load(Ordering::Relaxed);
+                for line in builder.captured_history()? {
+                    if line.kept {
+                        println!("{}", line.command);
+                    } else if color {
+                        println!("{}", line.command.strikethrough().dimmed());
+                    }
+                }
+            }
+            Command::Log { count } => {
+                let log = read_run_log(config, count)?;
+                if log.is_empty() {
+                    say!("No runs logged yet, set `run_log = true` in config.toml to log them");
+                }
+                for line in log {
+                    println!("{}", line);
+                }
+            }
+            Command::Stats { json } => {
+                let stats = stats(&get_scripts(config)?)?;
+                if !json && stats.scripts == 0 {
+                    say!("Looks like you don't have any scripts yet!");
+                    return Ok(());
+                }
+                if json {
+                    let json = serde_json::to_string_pretty(&stats).context("serialize stats")?;
+                    println!("{}", json);
+                    return Ok(());
+                }
+                let count = |n: usize| n.to_string().style(purpel).to_string();
+                println!(
+                    "{} scripts with {} lines in total",
+                    count(stats.scripts),
+                    count(stats.lines)
+                );
+                if let Some(largest) = stats.largest {
+                    println!(
+                        "The largest is `{}` with {} lines",
+                        largest.style(purpel),
+                        count(stats.largest_lines)
+                    );
+                }
+                println!("{} ask for input", count(stats.prompting));
+            }
+            Command::Status => {
+                let builds = build_statuses(config)?;
+                if builds.is_empty() {
+                    say!("No build in progress");
+                }
+                for (name, variables) in builds {
+                    println!(
+                        "Building `{}` with {} recorded variable(s) ^^",
+                        name.style(purpel),
+                        variables
+                    );
+                }
+            }
+            Command::Version { verbose } => {
+                println!("please {}", env!("CARGO_PKG_VERSION"));
+                if verbose {
+                    println!("commit: {}", env!("PLEASE_GIT_COMMIT"));
+                    println!("built: {}", env!("PLEASE_BUILD_DATE"));
+                    println!("target: {}", env!("PLEASE_TARGET"));
+                }
+            }
+            Command::Config { action } => match action {
+                ConfigAction::Get { key } => println!("{}", config.get(&key)?),
+                ConfigAction::Set { key, values } => {
+                    config.set(&key, &values)?;
+                    say!("Set `{}` ^^", key.style(purpel));
+                }
+                ConfigAction::Path => println!("{}", config.config_file_path.display()),
+            },
+            Command::Doctor => {
+                let checks = doctor(config);
+                for check in &checks {
+                    println!("{} {}", check_mark(check.ok), check.message);
+                }
+                ensure!(
+                    checks.iter().all(|check| check.ok),
+                    "Some checks failed, fix them to use please"
+                );
+            }
+            Command::Show { script, raw } => {
+                let script = Script::new(config, &script).context("parse script")?;
+                let contents = script.contents()?;
+                if raw {
+                    print!("{}", contents);
+                } else {
+                    say!("This is what `{}` looks like: ^^\n", script.style(purpel));
+                    let contents = contents.trim_end();
+                    if COLOR.load(Ordering::Relaxed) {
+                        println!("{}", highlight_sh(contents));
+                    } else {
+                        println!("{}", contents);
+                    }
+                }
+            }
+            Command::Which { script } => {
+                let script = Script::new(config, &script)?;
+                script.ensure_exists()?;
+                println!("{}", script.path().display());
+            }
+            Command::Edit {
+                script,
+                create,
+                force,
+                rename,
+            } => {
+                let script = Script::new(config, &script).context("parse script")?;
+                if let Some(rename) = rename {
+                    let dest = Script::new(config, &rename)?;
+                    if script.edit_as(config, &dest)? {
+                        say!(
+                            "Saved `{}` as `{}` ^^",
+                            script.style(purpel),
+                            dest.style(purpel)
+                        );
+                    } else {
+                        say!("Nothing saved, `{}` was not renamed", script.style(purpel));
+                    }
+                } else if !create {
+                    script.edit(config)?
+                } else if script.create(config, force)? {
+                    say!("Created script `{}` ^^", script.style(purpel));
+                } else {
+                    say!("Nothing saved, no script created");
+                }
+            }
+            Command::Undo { target } => {
+                let mut builder = ScriptBuilder::load_current(config, target.name())?;
+                match builder.exclude_last()? {
+                    Some(cmd) => {
+                        builder.save_replace()?;
+                        say!("Removed `{}` from the build ^^", cmd.style(purpel));
+                    }
+                    None => say!("There is nothing to undo"),
+                }
+            }
+            Command::Reset { target } => {
+                if target.name().is_none() && config.active_builds().is_empty() {
+                    say!("No build in progress to reset");
+                    return Ok(());
+                }
+                let builder = ScriptBuilder::load_current(config, target.name())?;
                 builder.delete_build()?;
-                println!("Build deleted ^^");
+                say!("Build deleted ^^");
             }
-            Command::Ask { words: _ } => {
-                let mut builder = ScriptBuilder::load_current()?;
-                let (var_name, var_expr, var_value) = ask_questions()?;
+            Command::Ask {
+                words: _,
+                kind,
+                default,
+                target,
+            } => {
+                let mut builder = ScriptBuilder::load_current(config, target.name())?;
+                let (var_name, var_expr, var_value) = ask_questions(kind, default.as_deref())?;
+                let var = Variable {
+                    value: var_name.clone(),
+                    expr: var_expr.clone(),
+                    kind,
+                    default,
+                    ..Default::default()
+                };
+                if !var.is_used() {
+                    eprintln!("Warning: `{}` does not use `${}`", var_expr, var_name);
+                }
+                if builder.asks_for(&var_name) {
+                    say!(
+                        "The script already asks for `{}`, it will reuse that answer ^^",
+                        var_name.style(purpel)
+                    );
+                }
 
                 // Add var to build cache
-                builder.add_var(var_name.clone(), var_expr.clone());
+                builder.add_var(var);
 
                 // Set var in env
                 std::env::set_var(var_name, var_value);
 
                 // Run command for user
-                std::process::Command::new("sh")
-                    .arg("-c")
-                    .arg(var_expr)
+                script::expr_command(var_expr)
                     .status()
-                    .expect("run command");
+                    .context("run ask expression")?;
 
                 // Save build cache
                 builder.save_replace()?;
             }
-            Command::Delete { script } => {
-                let script: Script = script.parse()?;
-                script.try_delete()?;
+            Command::Clean => {
+                let removed = clean_builds(config)?;
+                if removed.is_empty() {
+                    say!("Nothing to clean ^^");
+                }
+                for path in removed {
+                    println!("Removed {}", path.display());
+                }
+            }
+            Command::Note { words: _ } => {
+                // The note is read from history when the script is built
+                if config.active_builds().is_empty() {
+                    eprintln!("Warning: no build in progress, the note is not added to any script");
+                }
+            }
+            // The marks are read from history when the script is built, the start
+            // may come before the build so it is not checked
+            Command::MarkStart => {}
+            Command::MarkEnd => {
+                if config.active_builds().is_empty() {
+                    eprintln!("Warning: no build in progress, the mark is not used by any script");
+                }
+            }
+            Command::Remember { var, target } => {
+                ensure!(is_var_name(&var), "`{}` is not a valid variable name", var);
+                let value = env::var(&var)
+                    .with_context(|| format!("`{var}` is not set, make sure it is exported"))?;
+
+                let mut builder = ScriptBuilder::load_current(config, target.name())?;
+                builder.add_var(Variable {
+                    value: var.clone(),
+                    remembered: Some(value),
+                    ..Default::default()
+                });
+                builder.save_replace()?;
+                say!("The script will export `{}` ^^", var.style(purpel));
+            }
+            Command::Export {
+                script,
+                dest,
+                force,
+            } => {
+                let script = Script::new(config, &script)?;
+                let dest = dest.unwrap_or_else(|| PathBuf::from("."));
+                let dest = script.export(dest, force)?;
+                say!(
+                    "Exported script `{}` to {}",
+                    script.style(purpel),
+                    dest.display()
+                );
+            }
+            Command::Import { path, name } => {
+                let script = import_script(config, path, name)?;
+                say!("Imported script `{}` ^^", script.style(purpel));
+            }
+            Command::Alias { name, target } => {
+                let target = Script::new(config, &target)?;
+                let alias = Script::new(config, &name)?;
+                target.alias_as(&alias)?;
+                say!(
+                    "`{}` now runs `{}` ^^",
+                    alias.style(purpel),
+                    target.style(purpel)
+                );
+            }
+            Command::Rebuild { script, force } => {
+                ScriptBuilder::from_recipe(config, &script)?.rebuild(force)?;
+                say!("Rebuilt script `{}` ^^", script.style(purpel));
+            }
+            Command::Copy { src, dest } => {
+                let src = Script::new(config, &src)?;
+                let dest = Script::new(config, &dest)?;
+                src.copy_to(&dest)?;
+                say!(
+                    "Copied script `{}` to `{}` ^^",
+                    src.style(purpel),
+                    dest.style(purpel)
+                );
+            }
+            Command::Delete { scripts, yes } => {
+                let total = scripts.len();
+                let mut deleted = 0;
+                let mut failed = 0;
+                for name in scripts {
+                    match delete_script(config, &name, yes) {
+                        Ok(true) => deleted += 1,
+                        Ok(false) => {}
+                        // A single script fails the same as before
+                        Err(err) if total == 1 => return Err(err),
+                        Err(err) => {
+                            eprintln!("Error: {err:#}");
+                            failed += 1;
+                        }
+                    }
+                }
+                if total > 1 {
+                    say!("Deleted {} of {} scripts", deleted, total);
+                }
+                ensure!(failed == 0, "{} script(s) could not be deleted", failed);
             }
         };
 
@@ -111,7 +832,7 @@ impl Command {
 
 /// Ask user for variable name, expression and value
 /// and return them as a tuple in (name, expr, value) order
-fn ask_questions() -> anyhow::Result<(String, String, String)> {
+fn ask_questions(kind: VarKind, default: Option<&str>) -> anyhow::Result<(String, String, String)> {
     let var_name = Input::<String>::new()
         .with_prompt("Variable name?")
         .interact_text()
@@ -126,8 +847,13 @@ fn ask_questions() -> anyhow::Result<(String, String, String)> {
         .trim()
         .to_string();
 
-    let var_value = Input::<String>::new()
-        .with_prompt(format!("Value to use now?"))
+    let mut var_value = Input::<String>::new();
+    if let Some(default) = default {
+        var_value = var_value.with_initial_text(default);
+    }
+    let var_value = var_value
+        .with_prompt("Value to use now?")
+        .validate_with(|value: &String| kind.validate(value.trim()))
         .interact_text()
         .context("read var value")?
         .trim()
@@ -136,42 +862,738 @@ fn ask_questions() -> anyhow::Result<(String, String, String)> {
     Ok((var_name, var_expr, var_value))
 }
 
+/// Letters, digits and underscores, not starting with a digit
+fn is_var_name(name: &str) -> bool {
+    !name.starts_with(|c: char| c.is_ascii_digit())
+        && !name.is_empty()
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Starts the build, with `force` a build of the same name in progress is started over
+/// after confirming. Returns false if the user wants to keep the old one.
+fn start_build(builder: &ScriptBuilder, force: bool, yes: bool) -> anyhow::Result<bool> {
+    if !force || !builder.is_in_progress() {
+        builder.start_build()?;
+        return Ok(true);
+    }
+
+    let name = builder.get_script_name();
+    let prompt = format!(
+        "Throw away the build of `{}` in progress?",
+        name.style(highlight())
+    );
+    if !yes && !confirm(prompt)? {
+        say!("Okey, keeping it ^^");
+        return Ok(false);
+    }
+    builder.restart_build()?;
+
+    Ok(true)
+}
+
+fn ask_script_name() -> anyhow::Result<String> {
+    ensure!(
+        io::stdin().is_terminal(),
+        "Cannot ask for the script name without a terminal, give it as an argument"
+    );
+
+    let name = Input::<String>::new()
+        .with_prompt("Name of the script?")
+        .interact_text()
+        .context("read script name")?;
+
+    Ok(name.trim().to_string())
+}
+
+/// Lets the user fuzzy search for the script to run and returns its name
+fn pick_script(config: &Config) -> anyhow::Result<String> {
+    ensure!(
+        io::stdin().is_terminal(),
+        "Cannot pick a script without a terminal, give the name of the script to run"
+    );
+
+    let mut names = script_names(config);
+    ensure!(
+        !names.is_empty(),
+        "Looks like you don't have any scripts yet!"
+    );
+    names.sort();
+
+    let index = FuzzySelect::new()
+        .with_prompt("Which script?")
+        .items(&names)
+        .default(0)
+        .interact()
+        .context("pick script")?;
+
+    Ok(names.swap_remove(index))
+}
+
+/// Ask the user to confirm an action, defaults to no.
+/// Errors instead of prompting when stdin is not a terminal.
+fn confirm(prompt: impl Into<String>) -> anyhow::Result<bool> {
+    ensure!(
+        io::stdin().is_terminal(),
+        "Cannot ask for confirmation without a terminal, pass `--yes` to skip it"
+    );
+
+    Confirm::new()
+        .with_prompt(prompt)
+        .default(false)
+        .interact()
+        .context("read confirmation")
+}
+
+/// Deletes a script after confirming unless `yes`, returns false if it was kept
+fn delete_script(config: &Config, name: &str, yes: bool) -> anyhow::Result<bool> {
+    let purpel = highlight();
+    let script = Script::new(config, name)?;
+    script.ensure_exists()?;
+    let prompt = format!("Delete script `{}`?", script.style(purpel));
+    if !yes && !confirm(prompt)? {
+        say!("Okey, keeping `{}` ^^", script.style(purpel));
+        return Ok(false);
+    }
+    script.try_delete()?;
+    say!("Deleted script `{}`", script.style(purpel));
+
+    Ok(true)
+}
+
+/// Asks whether to keep, skip or edit a captured command, `None` skips it
+fn review_line(line: &str) -> anyhow::Result<Option<String>> {
+    let choice = Select::new()
+        .with_prompt(format!("`{}`", line))
+        .items(&["Keep", "Skip", "Edit"])
+        .default(0)
+        .interact()
+        .context("read choice")?;
+
+    match choice {
+        0 => Ok(Some(line.to_string())),
+        1 => Ok(None),
+        _ => Input::<String>::new()
+            .with_prompt("Command")
+            .with_initial_text(line)
+            .interact_text()
+            .map(Some)
+            .context("read command"),
+    }
+}
+
+/// `please <name>` runs a script unless `<name>` is a subcommand,
+/// `please run <name>` always runs a script
 #[derive(Parser, Debug)]
 #[command(version, about, arg_required_else_help = true)]
 struct Args {
+    #[arg(add = ArgValueCandidates::new(script_candidates))]
     script: Option<String>,
+    #[arg(
+        long,
+        requires = "script",
+        help = "Print the script instead of running it"
+    )]
+    dry_run: bool,
+    #[arg(
+        long,
+        value_name = "DIR",
+        requires = "script",
+        help = "Run the script in this directory"
+    )]
+    cwd: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "SECS",
+        requires = "script",
+        help = "Stop the script if it runs longer than this, 0 means no limit"
+    )]
+    timeout: Option<u64>,
+    #[arg(
+        long,
+        value_name = "KEY=VALUE",
+        requires = "script",
+        value_parser = parse_env_var,
+        help = "Set a variable for the script, can be repeated"
+    )]
+    env: Vec<(String, String)>,
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 1,
+        value_parser = clap::value_parser!(u32).range(1..),
+        requires = "script",
+        help = "Run the script N times, stopping at the first failed run"
+    )]
+    repeat: u32,
+    #[arg(
+        long,
+        requires = "script",
+        help = "Keep repeating the script after a failed run"
+    )]
+    keep_going: bool,
+    #[arg(long, global = true, help = "Print without colors")]
+    no_color: bool,
+    #[arg(short, long, global = true, help = "Only print the output of commands")]
+    quiet: bool,
+    #[arg(
+        long,
+        global = true,
+        help = "Use your own scripts even inside a project with a `.please` dir"
+    )]
+    global: bool,
     #[command(subcommand)]
     command: Option<Command>,
 }
 
+/// Picks one of the builds in progress when there are several
+#[derive(clap::Args, Debug)]
+struct BuildTarget {
+    #[arg(
+        long = "build",
+        value_name = "NAME",
+        help = "Build to use when several are in progress",
+        add = ArgValueCandidates::new(build_candidates)
+    )]
+    build: Option<String>,
+}
+
+impl BuildTarget {
+    fn name(&self) -> Option<&str> {
+        self.build.as_deref()
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigAction {
+    #[command(about = "Print the value of a setting")]
+    Get {
+        #[arg(help = "Name of the setting")]
+        key: String,
+    },
+    #[command(about = "Change a setting, lists take several values")]
+    Set {
+        #[arg(help = "Name of the setting")]
+        key: String,
+        #[arg(help = "New value of the setting", required = true)]
+        values: Vec<String>,
+    },
+    #[command(about = "Print the path of config.toml")]
+    Path,
+}
+
 #[derive(Subcommand, Debug)]
 enum Command {
-    #[command(about = "Run a script")]
+    #[command(about = "Run a script, pick one from a list if no name is given")]
     Run {
-        #[arg(help = "Name of the script you want to run")]
-        script: String,
+        #[arg(
+            help = "Name of the script you want to run",
+            add = ArgValueCandidates::new(script_candidates)
+        )]
+        script: Option<String>,
+        #[arg(long, help = "Print the script instead of running it")]
+        dry_run: bool,
+        #[arg(long, value_name = "DIR", help = "Run the script in this directory")]
+        cwd: Option<PathBuf>,
+        #[arg(
+            long,
+            value_name = "SECS",
+            help = "Stop the script if it runs longer than this, 0 means no limit"
+        )]
+        timeout: Option<u64>,
+        #[arg(
+            long,
+            value_name = "KEY=VALUE",
+            value_parser = parse_env_var,
+            help = "Set a variable for the script, can be repeated"
+        )]
+        env: Vec<(String, String)>,
+        #[arg(
+            long,
+            value_name = "N",
+            default_value_t = 1,
+            value_parser = clap::value_parser!(u32).range(1..),
+            help = "Run the script N times, stopping at the first failed run"
+        )]
+        repeat: u32,
+        #[arg(long, help = "Keep repeating the script after a failed run")]
+        keep_going: bool,
     },
     #[command(about = "Build current script")]
     Build {
-        #[arg(help = "Name of the script you want to create")]
+        #[arg(
+            help = "Name of the script you want to create",
+            conflicts_with = "build"
+        )]
         script: Option<String>,
+        #[arg(long, help = "Tag the script, can be repeated")]
+        tag: Vec<String>,
+        #[arg(long, help = "Shell used in the shebang, e.g. bash")]
+        shell: Option<String>,
+        #[arg(
+            long,
+            conflicts_with = "shell",
+            help = "Use zsh in the shebang if the script has zsh syntax"
+        )]
+        shell_aware: bool,
+        #[arg(
+            short,
+            long,
+            help = "Build even if the script has dangerous commands, or start over a build in progress"
+        )]
+        force: bool,
+        #[arg(
+            short,
+            long,
+            requires = "force",
+            help = "Start over a build in progress without asking for confirmation"
+        )]
+        yes: bool,
+        #[arg(
+            long,
+            conflicts_with_all = ["script", "tag", "shell", "shell_aware", "force", "yes", "name", "from", "histfile", "history_shell", "append"],
+            help = "Edit the current build before finishing it"
+        )]
+        edit: bool,
+        #[arg(
+            short,
+            long,
+            conflicts_with_all = ["script", "append", "edit"],
+            help = "Keep, skip or edit each captured command before finishing the build"
+        )]
+        interactive: bool,
+        #[arg(
+            long,
+            conflicts_with_all = ["script", "append"],
+            help = "Save the finished script under this name instead"
+        )]
+        name: Option<String>,
+        #[arg(
+            short,
+            long,
+            conflicts_with_all = ["script", "append", "edit"],
+            help = "Write the finished script to this file or directory instead of the scripts dir"
+        )]
+        output: Option<PathBuf>,
+        #[arg(
+            long,
+            value_name = "SCRIPT",
+            conflicts_with_all = ["script", "from", "build"],
+            add = ArgValueCandidates::new(script_candidates),
+            help = "Start a build that adds commands to the end of an existing script"
+        )]
+        append: Option<String>,
+        #[arg(
+            long,
+            value_name = "COUNT",
+            requires = "script",
+            help = "Build the script right away from the latest commands in history"
+        )]
+        from: Option<usize>,
+        #[arg(long, help = "Read commands from this history file instead of yours")]
+        histfile: Option<PathBuf>,
+        #[arg(long, value_enum, help = "Shell that wrote the history file")]
+        history_shell: Option<HistoryShell>,
+        #[command(flatten)]
+        target: BuildTarget,
     },
     #[command(about = "List created scripts")]
-    List,
+    List {
+        #[arg(long, help = "Only list scripts with this tag")]
+        tag: Option<String>,
+        #[arg(long, help = "Print the scripts as JSON")]
+        json: bool,
+        #[arg(
+            short,
+            long,
+            conflicts_with = "json",
+            help = "Show the size and last modified time of each script"
+        )]
+        long: bool,
+        #[arg(
+            long,
+            value_enum,
+            default_value_t,
+            requires = "long",
+            help = "Order of the long listing"
+        )]
+        sort: SortBy,
+        #[arg(
+            long,
+            conflicts_with_all = ["json", "long"],
+            help = "Print only the paths of the scripts"
+        )]
+        path: bool,
+        #[arg(
+            long,
+            conflicts_with_all = ["json", "long", "path"],
+            help = "Group the scripts under their tags"
+        )]
+        tree: bool,
+    },
+    #[command(about = "Build a script right away from your latest commands")]
+    Last {
+        #[arg(help = "Name of the script, asked for if not given")]
+        name: Option<String>,
+        #[arg(default_value_t = 10, help = "Number of commands to take")]
+        count: usize,
+        #[arg(short, long, help = "Build even if the script has dangerous commands")]
+        force: bool,
+    },
+    #[command(about = "Search scripts by name")]
+    Search {
+        #[arg(help = "Part of the script name")]
+        query: String,
+    },
     #[command(about = "Show what the current script looks like")]
-    Current,
+    Current {
+        #[arg(short, long, help = "Number the captured commands")]
+        numbered: bool,
+        #[arg(
+            short,
+            long,
+            visible_alias = "vars",
+            help = "Also print the variables of the build"
+        )]
+        all: bool,
+        #[command(flatten)]
+        target: BuildTarget,
+    },
+    #[command(about = "Print the history the current build captures")]
+    History {
+        #[command(flatten)]
+        target: BuildTarget,
+    },
+    #[command(about = "Print the latest runs from the run log")]
+    Log {
+        #[arg(
+            short = 'n',
+            long,
+            default_value_t = 10,
+            help = "Number of runs to print"
+        )]
+        count: usize,
+    },
+    #[command(about = "Tell whether a script is being built")]
+    Status,
+    #[command(about = "Summarize your scripts")]
+    Stats {
+        #[arg(long, help = "Print the numbers as JSON")]
+        json: bool,
+    },
+    #[command(about = "Check that your shell and history file work with please")]
+    Doctor,
+    #[command(about = "View and change the settings in config.toml")]
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    #[command(about = "Print the version of please")]
+    Version {
+        #[arg(short, long, help = "Also print the commit, build date and target")]
+        verbose: bool,
+    },
+    #[command(about = "Print a script without running it")]
+    Show {
+        #[arg(
+            help = "Name of the script",
+            add = ArgValueCandidates::new(script_candidates)
+        )]
+        script: String,
+        #[arg(long, help = "Print only the contents of the script")]
+        raw: bool,
+    },
+    #[command(about = "Print the path of a script")]
+    Which {
+        #[arg(
+            help = "Name of the script",
+            add = ArgValueCandidates::new(script_candidates)
+        )]
+        script: String,
+    },
     #[command(about = "Open a created script in editor")]
     Edit {
-        #[arg(help = "Name of the script")]
+        #[arg(
+            help = "Name of the script",
+            add = ArgValueCandidates::new(script_candidates)
+        )]
         script: String,
+        #[arg(long, help = "Write a new script from scratch instead")]
+        create: bool,
+        #[arg(long, requires = "create", help = "Overwrite the script if it exists")]
+        force: bool,
+        #[arg(
+            long,
+            value_name = "NEW_NAME",
+            conflicts_with = "create",
+            help = "Save the edited script under a new name"
+        )]
+        rename: Option<String>,
+    },
+    #[command(about = "Remove the last command from the current build")]
+    Undo {
+        #[command(flatten)]
+        target: BuildTarget,
     },
     #[command(about = "Reset script build")]
-    Reset,
+    Reset {
+        #[command(flatten)]
+        target: BuildTarget,
+    },
+    #[command(about = "Remove the files of all builds, even broken ones")]
+    Clean,
     #[command(about = "Add a prompt to your script")]
-    Ask { words: Vec<String> },
-    #[command(about = "Delete a script")]
+    Ask {
+        words: Vec<String>,
+        #[arg(
+            short = 't',
+            long = "type",
+            value_enum,
+            default_value_t,
+            help = "Kind of value to accept"
+        )]
+        kind: VarKind,
+        #[arg(short, long, help = "Value to use when the prompt is left empty")]
+        default: Option<String>,
+        #[command(flatten)]
+        target: BuildTarget,
+    },
+    #[command(about = "Add a comment to the script being built")]
+    Note {
+        #[arg(help = "Text of the comment", required = true)]
+        words: Vec<String>,
+    },
+    #[command(about = "Start capturing commands here instead of at the build start")]
+    MarkStart,
+    #[command(about = "Stop capturing commands here")]
+    MarkEnd,
+    #[command(about = "Export an environment variable with its current value in your script")]
+    Remember {
+        #[arg(help = "Name of the exported variable")]
+        var: String,
+        #[command(flatten)]
+        target: BuildTarget,
+    },
+    #[command(about = "Delete one or more scripts")]
     Delete {
-        #[arg(help = "Name of the script")]
+        #[arg(
+            help = "Names of the scripts",
+            required = true,
+            add = ArgValueCandidates::new(script_candidates)
+        )]
+        scripts: Vec<String>,
+        #[arg(short, long, help = "Delete without asking for confirmation")]
+        yes: bool,
+    },
+    #[command(about = "Build a script again from the commands it was first built from")]
+    Rebuild {
+        #[arg(
+            help = "Name of the script",
+            add = ArgValueCandidates::new(script_candidates)
+        )]
         script: String,
+        #[arg(short, long, help = "Build even if the script has dangerous commands")]
+        force: bool,
+    },
+    #[command(about = "Make a copy of a script under a new name")]
+    Copy {
+        #[arg(
+            help = "Name of the script to copy",
+            add = ArgValueCandidates::new(script_candidates)
+        )]
+        src: String,
+        #[arg(help = "Name of the new script")]
+        dest: String,
     },
+    #[command(about = "Create a short name that runs another script")]
+    Alias {
+        #[arg(help = "Name of the alias")]
+        name: String,
+        #[arg(
+            help = "Name of the script the alias runs",
+            add = ArgValueCandidates::new(script_candidates)
+        )]
+        target: String,
+    },
+    #[command(about = "Copy a script out of please")]
+    Export {
+        #[arg(
+            help = "Name of the script",
+            add = ArgValueCandidates::new(script_candidates)
+        )]
+        script: String,
+        #[arg(help = "Where to copy the script, defaults to the current directory")]
+        dest: Option<PathBuf>,
+        #[arg(short, long, help = "Overwrite the destination if it exists")]
+        force: bool,
+    },
+    #[command(about = "Copy an existing script into please")]
+    Import {
+        #[arg(help = "Path to the script file")]
+        path: PathBuf,
+        #[arg(help = "Name for the script, defaults to the file name")]
+        name: Option<String>,
+    },
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn run_script_named_like_subcommand() {
+        let args = Args::try_parse_from(["please", "run", "build"]).unwrap();
+        assert!(args.script.is_none());
+        assert!(matches!(
+            args.command,
+            Some(Command::Run { script, .. }) if script.as_deref() == Some("build")
+        ));
+    }
+
+    #[test]
+    fn parse_env_vars() {
+        let args = Args::try_parse_from([
+            "please", "run", "foo", "--env", "A=1", "--env", "B=x=y", "--env", "C=",
+        ])
+        .unwrap();
+        let Some(Command::Run { env, .. }) = args.command else {
+            panic!("should parse run");
+        };
+        let expected = [("A", "1"), ("B", "x=y"), ("C", "")]
+            .map(|(key, value)| (key.to_string(), value.to_string()));
+        assert_eq!(env, expected);
+
+        assert!(Args::try_parse_from(["please", "run", "foo", "--env", "A"]).is_err());
+        assert!(Args::try_parse_from(["please", "run", "foo", "--env", "=1"]).is_err());
+        assert!(Args::try_parse_from(["please", "foo", "--env", "A=1"]).is_ok());
+    }
+
+    #[test]
+    fn prefer_subcommand_over_script() {
+        let args = Args::try_parse_from(["please", "build"]).unwrap();
+        assert!(args.script.is_none());
+        assert!(matches!(args.command, Some(Command::Build { .. })));
+
+        let args = Args::try_parse_from(["please", "foo"]).unwrap();
+        assert_eq!(args.script.as_deref(), Some("foo"));
+        assert!(args.command.is_none());
+    }
+
+    #[test]
+    fn disable_colors() {
+        assert!(use_color(false, None, true));
+        assert!(!use_color(true, None, true));
+        assert!(!use_color(false, Some("1".into()), true));
+        assert!(use_color(false, Some("".into()), true));
+        assert!(!use_color(false, None, false));
+    }
+
+    #[test]
+    fn align_variable_rows() {
+        let variables = [
+            Variable {
+                value: "NAME".to_string(),
+                expr: "echo hi $NAME".to_string(),
+                ..Default::default()
+            },
+            Variable {
+                value: "DIR".to_string(),
+                expr: "please remember DIR".to_string(),
+                remembered: Some("/tmp".to_string()),
+                ..Default::default()
+            },
+        ];
+        let rows = variable_rows(&variables);
+        assert_eq!(rows[0], ("NAME".to_string(), "echo hi $NAME".to_string()));
+        assert_eq!(
+            rows[1],
+            ("DIR ".to_string(), "remembered as `/tmp`".to_string())
+        );
+
+        let args = Args::try_parse_from(["please", "current", "--vars"]).unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Command::Current { all: true, .. })
+        ));
+    }
+
+    #[test]
+    fn accept_quiet_anywhere() {
+        let args = Args::try_parse_from(["please", "-q", "list"]).unwrap();
+        assert!(args.quiet);
+        assert!(args.script.is_none());
+        assert!(matches!(args.command, Some(Command::List { .. })));
+
+        let args = Args::try_parse_from(["please", "--no-color", "list"]).unwrap();
+        assert!(matches!(args.command, Some(Command::List { .. })));
+
+        assert!(
+            Args::try_parse_from(["please", "list", "--quiet"])
+                .unwrap()
+                .quiet
+        );
+        assert!(Args::try_parse_from(["please", "-q", "foo"]).unwrap().quiet);
+        assert!(!Args::try_parse_from(["please", "foo"]).unwrap().quiet);
+    }
+
+    #[test]
+    fn delete_several_scripts() {
+        let args = Args::try_parse_from(["please", "delete", "a", "b", "-y"]).unwrap();
+        let Some(Command::Delete { scripts, yes }) = args.command else {
+            panic!("expected delete");
+        };
+        assert_eq!(scripts, ["a", "b"]);
+        assert!(yes);
+
+        assert!(Args::try_parse_from(["please", "delete"]).is_err());
+    }
+
+    #[test]
+    fn repeat_runs() {
+        let config = Config::from_base_dir("/tmp/main1").unwrap();
+        let script = Script::new(&config, "count").unwrap();
+        let count = "/tmp/main1/count.txt";
+        // Fails on the second run
+        fs::write(
+            script.path(),
+            format!("echo x >> {count}\n[ $(wc -l < {count}) -ne 2 ]\n"),
+        )
+        .unwrap();
+        let run = |keep_going| {
+            let _ = fs::remove_file(count);
+            let repeat = Repeat {
+                times: 3,
+                keep_going,
+            };
+            let result = parse_and_run(
+                &config,
+                "count".to_string(),
+                false,
+                Default::default(),
+                repeat,
+            );
+            (result, fs::read_to_string(count).unwrap().lines().count())
+        };
+
+        let (result, runs) = run(false);
+        assert!(result.unwrap_err().downcast_ref::<ScriptFailed>().is_some());
+        assert_eq!(runs, 2);
+        let (result, runs) = run(true);
+        assert!(result.is_err());
+        assert_eq!(runs, 3);
+
+        assert!(Args::try_parse_from(["please", "run", "count", "--repeat", "0"]).is_err());
+        assert!(Args::try_parse_from(["please", "count", "--repeat", "2"]).is_ok());
+
+        fs::remove_dir_all("/tmp/main1").unwrap()
+    }
+
+    #[test]
+    fn validate_var_names() {
+        assert!(is_var_name("API_TOKEN"));
+        assert!(is_var_name("_x1"));
+        assert!(!is_var_name(""));
+        assert!(!is_var_name("1x"));
+        assert!(!is_var_name("FOO=bar"));
+    }
 }